@@ -0,0 +1,96 @@
+pub use analysis::reachable_instructions;
+pub use analysis::successors;
+pub use analysis::stack_depth;
+
+pub mod analysis {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::emulator::cpu::LOAD_ADDRESS;
+
+    /// Follows the control flow of a ROM from its entry point, returning every instruction
+    /// that can be reached along with its address, in address order. Computed jumps (BNNN)
+    /// can't be followed, so code only reachable through them is missed.
+    pub fn reachable_instructions(rom: &[u8]) -> Vec<(usize, u16)> {
+        let end = LOAD_ADDRESS + rom.len();
+        let mut visited = BTreeMap::new();
+        let mut pending = vec![LOAD_ADDRESS];
+
+        while let Some(addr) = pending.pop() {
+            if addr < LOAD_ADDRESS || addr + 1 >= end || visited.contains_key(&addr) {
+                continue;
+            }
+            let offset = addr - LOAD_ADDRESS;
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            visited.insert(addr, opcode);
+            pending.extend(successors(addr, opcode));
+        }
+
+        visited.into_iter().collect()
+    }
+
+    /// Returns the addresses execution may continue at after the instruction at `addr`.
+    pub fn successors(addr: usize, opcode: u16) -> Vec<usize> {
+        let nnn = (opcode & 0xfff) as usize;
+        match (opcode >> 12, (opcode >> 8) & 0xf, (opcode >> 4) & 0xf, opcode & 0xf) {
+            (0, 0, 0xe, 0xe) | (0, 0, 0xf, 0xd) | (0xb, _, _, _) => vec![],
+            (1, _, _, _) => vec![nnn],
+            (2, _, _, _) => vec![nnn, addr + 2],
+            (3, _, _, _) | (4, _, _, _) | (5, _, _, 0) | (9, _, _, 0)
+                | (0xe, _, 9, 0xe) | (0xe, _, 0xa, 1) => vec![addr + 2, addr + 4],
+            // XO-CHIP's F000 NNNN is the only four byte instruction
+            (0xf, 0, 0, 0) => vec![addr + 4],
+            _ => vec![addr + 2],
+        }
+    }
+
+    /// Estimates how deeply subroutine calls nest, following them from the entry point.
+    /// Returns `None` if a subroutine can end up calling itself, in which case the depth
+    /// depends on the program's data.
+    pub fn stack_depth(rom: &[u8]) -> Option<usize> {
+        nested_calls(rom, LOAD_ADDRESS, &mut Vec::new(), &mut BTreeMap::new())
+    }
+
+    fn nested_calls(rom: &[u8], routine: usize, active: &mut Vec<usize>, depths: &mut BTreeMap<usize, usize>) -> Option<usize> {
+        if let Some(depth) = depths.get(&routine) {
+            return Some(*depth);
+        }
+        if active.contains(&routine) {
+            return None;
+        }
+
+        active.push(routine);
+        let mut depth = 0;
+        for callee in callees(rom, routine) {
+            depth = depth.max(1 + nested_calls(rom, callee, active, depths)?);
+        }
+        active.pop();
+
+        depths.insert(routine, depth);
+        Some(depth)
+    }
+
+    /// Returns the subroutines called by the code reachable from `routine` without
+    /// returning from it.
+    fn callees(rom: &[u8], routine: usize) -> BTreeSet<usize> {
+        let end = LOAD_ADDRESS + rom.len();
+        let mut visited = BTreeSet::new();
+        let mut callees = BTreeSet::new();
+        let mut pending = vec![routine];
+
+        while let Some(addr) = pending.pop() {
+            if addr < LOAD_ADDRESS || addr + 1 >= end || !visited.insert(addr) {
+                continue;
+            }
+            let offset = addr - LOAD_ADDRESS;
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            if opcode >> 12 == 2 {
+                callees.insert((opcode & 0xfff) as usize);
+                pending.push(addr + 2);
+            } else {
+                pending.extend(successors(addr, opcode));
+            }
+        }
+
+        callees
+    }
+}
@@ -0,0 +1,130 @@
+pub use audio::Buzzer;
+pub use audio::WavRecorder;
+
+pub mod audio {
+    use std::{fs::File, io::{BufWriter, Seek, SeekFrom, Write}};
+    use sdl2::Sdl;
+    use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+    use crate::emulator::Chip8Result;
+
+    const SAMPLE_RATE: u32 = 44100;
+    const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+    const TONE_HZ: f32 = 440.0;
+    const VOLUME: f32 = 0.25;
+
+    struct SquareWave {
+        phase: f32,
+        phase_inc: f32,
+    }
+
+    impl SquareWave {
+        fn next_sample(&mut self) -> f32 {
+            let sample = if self.phase < 0.5 { VOLUME } else { -VOLUME };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+            sample
+        }
+    }
+
+    impl AudioCallback for SquareWave {
+        type Channel = f32;
+
+        fn callback(&mut self, out: &mut [f32]) {
+            for sample in out.iter_mut() {
+                *sample = self.next_sample();
+            }
+        }
+    }
+
+    /// Plays a tone while the sound timer is active.
+    pub struct Buzzer {
+        device: AudioDevice<SquareWave>,
+        playing: bool,
+    }
+
+    impl Buzzer {
+        pub fn new(sdl_context: &Sdl) -> Chip8Result<Buzzer> {
+            let desired = AudioSpecDesired {
+                freq: Some(SAMPLE_RATE as i32),
+                channels: Some(1),
+                samples: None,
+            };
+            let device = sdl_context.audio()?.open_playback(None, &desired, |spec| {
+                SquareWave { phase: 0.0, phase_inc: TONE_HZ / spec.freq as f32 }
+            })?;
+
+            Ok(Buzzer { device, playing: false })
+        }
+
+        pub fn update(&mut self, playing: bool) {
+            if playing != self.playing {
+                if playing { self.device.resume(); } else { self.device.pause(); }
+                self.playing = playing;
+            }
+        }
+    }
+
+    /// Writes the buzzer's output to a 16-bit mono WAV file, one 60Hz frame at a time,
+    /// so the recording follows emulated time rather than the host's audio clock.
+    pub struct WavRecorder {
+        writer: BufWriter<File>,
+        wave: SquareWave,
+        samples: u32,
+    }
+
+    impl WavRecorder {
+        pub fn create(filename: &str) -> Chip8Result<WavRecorder> {
+            let mut recorder = WavRecorder {
+                writer: BufWriter::new(File::create(filename)?),
+                wave: SquareWave { phase: 0.0, phase_inc: TONE_HZ / SAMPLE_RATE as f32 },
+                samples: 0,
+            };
+            recorder.write_header()?;
+            Ok(recorder)
+        }
+
+        fn write_header(&mut self) -> Chip8Result<()> {
+            let data_size = self.samples * 2;
+            let w = &mut self.writer;
+            w.write_all(b"RIFF")?;
+            w.write_all(&(36 + data_size).to_le_bytes())?;
+            w.write_all(b"WAVEfmt ")?;
+            w.write_all(&16u32.to_le_bytes())?;
+            w.write_all(&1u16.to_le_bytes())?;
+            w.write_all(&1u16.to_le_bytes())?;
+            w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+            w.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+            w.write_all(&2u16.to_le_bytes())?;
+            w.write_all(&16u16.to_le_bytes())?;
+            w.write_all(b"data")?;
+            w.write_all(&data_size.to_le_bytes())?;
+            Ok(())
+        }
+
+        pub fn record_frame(&mut self, playing: bool) -> Chip8Result<()> {
+            for _ in 0..SAMPLES_PER_FRAME {
+                let sample = if playing { self.wave.next_sample() } else { 0.0 };
+                self.writer.write_all(&((sample * i16::MAX as f32) as i16).to_le_bytes())?;
+            }
+            self.samples += SAMPLES_PER_FRAME;
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Chip8Result<()> {
+            self.writer.seek(SeekFrom::Start(0))?;
+            self.write_header()?;
+            self.writer.flush()?;
+            Ok(())
+        }
+    }
+
+    impl Drop for WavRecorder {
+        /// Fills in the sizes in the header, which aren't known until recording stops.
+        /// This happens on drop so the file is usable even if emulation ends with an error.
+        fn drop(&mut self) {
+            if let Err(e) = self.finish() {
+                eprintln!("Could not finish audio recording: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+pub use batch::test_dir;
+pub use batch::run_headless;
+
+pub mod batch {
+    use std::{fs, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}};
+    use rayon::prelude::*;
+
+    use crate::emulator::{Chip8Result, cpu::{CPU, Event}, dump::{self, Trace}, engine::Chip8Core, rom::{self, RomFormat, RomHashes}, settings::Settings, signal, variants::{Detection, Platform}};
+
+    /// Instructions executed between timer ticks, i.e. the default speed of 600 per second
+    const CYCLES_PER_TIMER_TICK: u64 = 10;
+
+    enum Outcome {
+        /// Ran for all the cycles it was given
+        Ran,
+        Halted(usize),
+        Failed(String),
+    }
+
+    struct Report {
+        name: String,
+        outcome: Outcome,
+        /// CRC32 of the final display, for spotting changes between versions
+        display_crc32: Option<u32>,
+    }
+
+    /// Runs every ROM in `dir` without a window for `cycles` instructions, in parallel,
+    /// and prints how each of them ended up. ROMs use the platform and quirks from the
+    /// command line and the files they came in, but never the settings remembered for
+    /// them, so results are the same on every machine.
+    pub fn test_dir(dir: &str, cycles: u64, overrides: &Settings) -> Chip8Result<()> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.retain(|path| path.is_file());
+        paths.sort();
+
+        let reports: Vec<Report> = paths.par_iter().map(|path| test_rom(path, cycles, overrides)).collect();
+
+        let width = reports.iter().map(|report| report.name.len()).max().unwrap_or(0).max(3);
+        println!("{:<width$}  {:<8}  OUTCOME", "ROM", "DISPLAY", width = width);
+        for report in reports.iter() {
+            let display = match report.display_crc32 {
+                Some(crc32) => format!("{:08x}", crc32),
+                None => "-".to_string(),
+            };
+            let outcome = match &report.outcome {
+                Outcome::Ran => "ran".to_string(),
+                Outcome::Halted(addr) => format!("halted at 0x{:03x}", addr),
+                Outcome::Failed(error) => format!("failed: {}", error.lines().next().unwrap_or("")),
+            };
+            println!("{:<width$}  {:<8}  {}", report.name, display, outcome, width = width);
+        }
+
+        let failed = reports.iter().filter(|report| matches!(report.outcome, Outcome::Failed(_))).count();
+        println!("\n{} ROMs, {} ran, {} halted, {} failed", reports.len(),
+            reports.iter().filter(|report| matches!(report.outcome, Outcome::Ran)).count(),
+            reports.iter().filter(|report| matches!(report.outcome, Outcome::Halted(_))).count(),
+            failed);
+        Ok(())
+    }
+
+    fn test_rom(path: &Path, cycles: u64, overrides: &Settings) -> Report {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut display_crc32 = None;
+        // A panic is a bug in rschip8 rather than in the ROM, but it shouldn't stop the other ROMs
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run_rom(path, cycles, overrides, &mut display_crc32)));
+        let outcome = match result {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(e)) => Outcome::Failed(e.to_string()),
+            Err(panic) => Outcome::Failed(format!("panicked: {}", panic.downcast_ref::<&str>().cloned()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause"))),
+        };
+        Report { name, outcome, display_crc32 }
+    }
+
+    /// Runs a program without a window for `cycles` instructions, then prints its display
+    /// as text, for checking a ROM from a terminal. SIGUSR1 dumps the machine meanwhile.
+    pub fn run_headless(program: &str, format: Option<RomFormat>, cycles: u64, overrides: &Settings) -> Chip8Result<()> {
+        let mut cpu = load(program, format, overrides, true)?;
+        let mut trace = Trace::new();
+        signal::install_dump_handler()?;
+        let outcome = run(&mut cpu, cycles, |cpu| {
+            trace.record(cpu);
+            signal::dump_if_requested(cpu, &trace);
+        });
+        match outcome {
+            Outcome::Ran => eprintln!("Ran {} instructions", cycles),
+            Outcome::Halted(addr) => eprintln!("Halted at 0x{:03x}", addr),
+            Outcome::Failed(error) => eprintln!("Failed: {}", error),
+        }
+        print!("{}", dump::format_display(cpu.display()));
+        Ok(())
+    }
+
+    fn run_rom(path: &Path, cycles: u64, overrides: &Settings, display_crc32: &mut Option<u32>) -> Chip8Result<Outcome> {
+        let mut cpu = load(&path.to_string_lossy(), None, overrides, false)?;
+        let outcome = run(&mut cpu, cycles, |_| {});
+
+        let rows: Vec<u8> = cpu.display().rows().iter().flat_map(|row| row.to_be_bytes()).collect();
+        *display_crc32 = Some(crc32fast::hash(&rows));
+        Ok(outcome)
+    }
+
+    /// Loads a program with the platform, quirks and display it would use when run normally,
+    /// leaving out the settings remembered for it unless `remembered` is set.
+    fn load(program: &str, format: Option<RomFormat>, overrides: &Settings, remembered: bool) -> Chip8Result<CPU> {
+        let loaded = rom::load(program, format)?;
+        let rom = loaded.program;
+        let mut cpu = CPU::from_bytes(&rom)?;
+        let mut settings = loaded.settings;
+        if remembered {
+            settings = settings.merge(Settings::load(&RomHashes::of(&rom).sha1)?);
+        }
+        let settings = settings.merge(overrides.clone());
+        let platform = match settings.platform()? {
+            Some(platform) => platform,
+            None => Detection::scan(&rom).map(|detection| detection.platform).unwrap_or(Platform::Chip8),
+        };
+        let mut quirks = platform.quirks();
+        settings.apply_quirks(&mut quirks)?;
+        cpu.set_platform(platform);
+        cpu.set_quirks(quirks);
+        cpu.set_display_height(settings.display_height()?);
+        Ok(cpu)
+    }
+
+    /// Runs `core` for `cycles` instructions, calling `before_step` before each of them.
+    fn run<C: Chip8Core>(core: &mut C, cycles: u64, mut before_step: impl FnMut(&C)) -> Outcome {
+        let mut outcome = Outcome::Ran;
+        for cycle in 0..cycles {
+            if cycle % CYCLES_PER_TIMER_TICK == 0 {
+                core.tick_timers();
+            }
+            before_step(core);
+            match core.step() {
+                Ok(Some(Event::Halted)) => {
+                    outcome = Outcome::Halted(core.pc());
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    outcome = Outcome::Failed(e.to_string());
+                    break;
+                }
+            }
+        }
+        outcome
+    }
+}
@@ -0,0 +1,134 @@
+pub use bundle::parse;
+pub use bundle::MAGIC;
+
+pub mod bundle {
+    use std::convert::TryInto;
+
+    use crate::emulator::{Chip8Result, rom::Rom, settings::{self, Settings}, variants::Platform};
+
+    /// The start of every bundle
+    pub const MAGIC: &[u8; 4] = b"RC8B";
+    const VERSION: u8 = 0;
+
+    const TITLE: u8 = 0x01;
+    const AUTHOR: u8 = 0x02;
+    const PLATFORM: u8 = 0x03;
+    const QUIRKS: u8 = 0x04;
+    const FOREGROUND: u8 = 0x05;
+    const BACKGROUND: u8 = 0x06;
+    const PROGRAM: u8 = 0x10;
+
+    /// Parses a bundle, rschip8's own container for a program and the settings it runs with:
+    /// the magic and a version byte, followed by fields made of a tag byte, a big-endian `u16`
+    /// length and that many bytes of data. Text fields are UTF-8. Unknown tags are skipped,
+    /// so newer files still load.
+    pub fn parse(bytes: &[u8]) -> Chip8Result<Rom> {
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("Not a rschip8 bundle".into());
+        }
+        let version = bytes[MAGIC.len()];
+        if version != VERSION {
+            return Err(format!("Unsupported bundle version: {}", version).into());
+        }
+
+        let mut program = None;
+        let mut title = None;
+        let mut author = None;
+        let mut settings = Settings::default();
+
+        let mut rest = &bytes[MAGIC.len() + 1..];
+        while !rest.is_empty() {
+            if rest.len() < 3 {
+                return Err("Truncated bundle field".into());
+            }
+            let tag = rest[0];
+            let len = u16::from_be_bytes(rest[1..3].try_into().unwrap()) as usize;
+            if rest.len() < 3 + len {
+                return Err(format!("Truncated bundle field {:02x}", tag).into());
+            }
+            let data = &rest[3..3 + len];
+            rest = &rest[3 + len..];
+
+            let text = || std::str::from_utf8(data).map(str::to_string);
+            match tag {
+                TITLE => title = Some(text()?),
+                AUTHOR => author = Some(text()?),
+                PLATFORM => {
+                    let name = text()?;
+                    Platform::from_name(&name)?;
+                    settings.platform = Some(name);
+                }
+                // Comma separated settings like `shift_vy=on,clip_sprites=off`
+                QUIRKS => {
+                    let quirks = text()?;
+                    for setting in quirks.split(',').filter(|setting| !setting.is_empty()) {
+                        let invalid = || format!("Invalid quirk in bundle: {}, expected <NAME>=<on|off>", setting);
+                        let (name, value) = setting.split_once('=').ok_or_else(invalid)?;
+                        let value = match value {
+                            "on" => true,
+                            "off" => false,
+                            _ => return Err(invalid().into()),
+                        };
+                        Platform::Chip8.quirks().get(name)?;
+                        settings.quirks.insert(name.to_string(), value);
+                    }
+                }
+                FOREGROUND => {
+                    let color = text()?;
+                    settings::parse_color(&color)?;
+                    settings.foreground = Some(color);
+                }
+                BACKGROUND => {
+                    let color = text()?;
+                    settings::parse_color(&color)?;
+                    settings.background = Some(color);
+                }
+                PROGRAM => program = Some(data.to_vec()),
+                _ => {}
+            }
+        }
+
+        Ok(Rom {
+            program: program.ok_or("The bundle has no program")?,
+            title,
+            author,
+            settings,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A program drawing the letter A, with a field of an unknown tag before it
+        const SAMPLE: &[u8] = include_bytes!("testdata/sample.rc8");
+
+        #[test]
+        fn parses_sample_file() {
+            let rom = parse(SAMPLE).unwrap();
+            assert_eq!(rom.title.as_deref(), Some("Letter A"));
+            assert_eq!(rom.author.as_deref(), Some("rschip8"));
+            assert_eq!(rom.settings.platform.as_deref(), Some("schip"));
+            assert_eq!(rom.settings.quirks.get("shift_vy"), Some(&false));
+            assert_eq!(rom.settings.quirks.get("clip_sprites"), Some(&true));
+            assert_eq!(rom.settings.foreground.as_deref(), Some("#ffcc00"));
+            assert_eq!(rom.settings.background.as_deref(), Some("#202020"));
+            assert_eq!(rom.program.len(), 17);
+            assert_eq!(&rom.program[..2], &[0x00, 0xe0]);
+        }
+
+        #[test]
+        fn rejects_invalid_quirk_values() {
+            let mut bytes = b"RC8B\x00\x04\x00\x0bshift_vy=no\x10\x00\x02\x00\xe0".to_vec();
+            assert!(parse(&bytes).is_err());
+            bytes[8..19].copy_from_slice(b"shift_vy=on");
+            assert!(parse(&bytes).is_ok());
+        }
+
+        #[test]
+        fn rejects_truncated_fields() {
+            assert!(parse(&SAMPLE[..SAMPLE.len() - 1]).is_err());
+            assert!(parse(b"RC8B\x01").is_err());
+        }
+    }
+}
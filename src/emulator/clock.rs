@@ -0,0 +1,44 @@
+pub use clock::TimerClock;
+
+pub mod clock {
+    use sdl2::TimerSubsystem;
+
+    /// Ticks the clock lets pile up at normal speed, after which time is dropped rather
+    /// than caught up on, e.g. while the window is being dragged. It grows with the rate,
+    /// or turbo would be held back to a few ticks per wakeup.
+    const MAX_PENDING_TICKS: f64 = 6.0;
+
+    /// Tells when the delay and sound timers are due from SDL's high resolution counter,
+    /// so they tick at 60Hz in real time whatever the speed and however late the emulator
+    /// wakes up.
+    pub struct TimerClock {
+        timer: TimerSubsystem,
+        last: u64,
+        pending: f64,
+    }
+
+    impl TimerClock {
+        pub fn new(timer: TimerSubsystem) -> TimerClock {
+            let last = timer.performance_counter();
+            TimerClock { timer, last, pending: 0.0 }
+        }
+
+        /// The number of ticks due since the last call, with time running `rate` times as
+        /// fast as it really does, for turbo and slow motion.
+        pub fn due(&mut self, rate: f64) -> u32 {
+            let now = self.timer.performance_counter();
+            let elapsed = now.saturating_sub(self.last) as f64 / self.timer.performance_frequency() as f64;
+            self.last = now;
+
+            self.pending = (self.pending + elapsed * 60.0 * rate).min(MAX_PENDING_TICKS * rate.max(1.0));
+            let due = self.pending.floor();
+            self.pending -= due;
+            due as u32
+        }
+
+        /// Lets time pass without any ticks being due, while emulation is suspended.
+        pub fn skip(&mut self) {
+            self.last = self.timer.performance_counter();
+        }
+    }
+}
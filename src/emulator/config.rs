@@ -0,0 +1,313 @@
+pub use config::Config;
+pub use config::USAGE;
+pub use config::AutoSave;
+pub use config::Command;
+
+pub mod config {
+    use sdl2::keyboard::Keycode;
+
+    use crate::emulator::{Chip8Result, rom::RomFormat, settings::{self, Settings}, variants::Platform};
+
+    pub const USAGE: &str = "Usage: rschip8 [OPTIONS] [PROGRAM]
+       rschip8 --list-platforms
+       rschip8 --list-quirks
+       rschip8 test-dir [--cycles <N>] <DIRECTORY>
+       rschip8 lint [--format <FORMAT>] <PROGRAM>
+       rschip8 stats [--format <FORMAT>] <PROGRAM>
+       rschip8 --headless [--cycles <N>] [OPTIONS] <PROGRAM>
+       rschip8 attract [--attract-time <SECONDS>] [OPTIONS] <DIRECTORY>
+
+Options:
+    --format <FORMAT>       How the program is encoded: binary, hex or bundle (default: binary or hex, detected)
+    --speed <N>             Instructions executed per second (default: 600)
+    --turbo-key <KEY>       Key that fast-forwards emulation while held (default: Tab)
+    --turbo-factor <N>      Speed multiplier applied while turbo is held (default: 8)
+    --slowmo-key <KEY>      Key that toggles slow motion (default: F3)
+    --slowmo-factor <N>     Speed multiplier applied in slow motion (default: 0.25)
+    --run-ahead <N>         Frames to emulate ahead of the display to hide input latency (default: 0)
+    --foreground <COLOR>    Color of lit pixels as #rrggbb (default: #ffffff)
+    --background <COLOR>    Color of unlit pixels as #rrggbb (default: #000000)
+    --invert                Swap the foreground and background colors (toggle with F10)
+    --lcd                   Dark pixels on pale green with ghosting, like an old LCD (toggle with F11)
+    --key <KEY>=<0-F>       Maps a host key to a CHIP-8 key, can be repeated
+    --macro <KEY>=<STEPS>   Plays a sequence of CHIP-8 key presses when a host key is pressed,
+                            e.g. 5:2,_:10,6:2 (key:frames, _ waits), can be repeated
+    --autofire <KEY>=<N>    Presses the CHIP-8 key mapped to a host key N times per second
+                            while it's held (N up to 30), can be repeated
+    --display <SIZE>        Size of the display: 64x32, 64x48 (ETI-660) or 64x64 (two-page VIP)
+                            (default: 64x32)
+    --platform <NAME>       Platform whose quirks to use: chip8, vip, schip, xochip or megachip
+                            (default: detected from the ROM, or chip8)
+    --quirk <NAME>=<on|off> Turns a single quirk on or off, can be repeated
+    --auto-save <MODE>      Save on exit and resume on the next launch: off, ask or resume (default: off)
+    --record-audio <FILE>   Write the buzzer's output to a WAV file
+    --mute                  Don't play the buzzer
+    --no-rumble             Don't rumble controllers while the buzzer sounds
+    --start-paused          Pause in the debugger before the first instruction runs
+    --dump-on-exit <FILE>   Write the registers, display and memory to a file on exit
+    --replay <FILE>         Play back a replay saved with F6, then carry on from where it ends
+
+Without a program, a file picker is shown to choose one.
+
+Commands:
+    --list-platforms        List the platforms and the quirks each of them uses
+    --list-quirks           List the quirks and their defaults on each platform
+    test-dir <DIRECTORY>    Run every ROM in a directory without a window, in parallel, and report
+                            how each one ended up and a hash of its final display
+    --headless              Run the program without a window, then print its display as text
+    --cycles <N>            Instructions test-dir and --headless run programs for (default: 100000)
+    lint <PROGRAM>          Look for code that depends on quirks, is never reached, jumps out of
+                            the program or reads past the end of memory, and for deep calls
+    stats <PROGRAM>         Show an opcode histogram, how much the program draws, calls and uses
+                            random numbers, the platform it needs and its stack depth
+    attract <DIRECTORY>     Show the ROMs in a directory one after another, playing back their
+                            latest replay if they have one, until a key is pressed to play
+    --attract-time <N>      Seconds attract shows each ROM for (default: 30)
+
+Speed, run-ahead, colors, display size, keys, macros, autofire, platform and quirks given for a ROM
+are remembered and used the next time it is loaded, except with attract.";
+
+    const DEFAULT_TURBO_KEY: Keycode = Keycode::Tab;
+    const DEFAULT_SLOWMO_KEY: Keycode = Keycode::F3;
+    const MAX_RUN_AHEAD: u32 = 8;
+    /// A press every two frames is as fast as a key can be hammered at 60 frames per second
+    const MAX_AUTOFIRE_RATE: u32 = 30;
+    const DEFAULT_CYCLES: u64 = 100_000;
+    const DEFAULT_ATTRACT_TIME: u64 = 30;
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum AutoSave {
+        Off,
+        Ask,
+        Resume,
+    }
+
+    /// What rschip8 was asked to do. The list commands don't use the program.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum Command {
+        Run,
+        ListPlatforms,
+        ListQuirks,
+        /// Runs the ROMs in the directory given as the program
+        TestDir,
+        /// Checks the program for constructs that aren't portable or look like mistakes
+        Lint,
+        /// Prints statistics about the program's code
+        Stats,
+        /// Runs the program without a window and prints its display
+        Headless,
+        /// Cycles through the ROMs in the directory given as the program until a key is pressed
+        Attract,
+    }
+
+    pub struct Config {
+        pub command: Command,
+        /// The program to run. When none is given, one is picked with a file dialog.
+        pub program: Option<String>,
+        /// How the program is encoded, or `None` to detect it
+        pub rom_format: Option<RomFormat>,
+        pub turbo_key: Keycode,
+        pub slowmo_key: Keycode,
+        pub auto_save: AutoSave,
+        pub record_audio: Option<String>,
+        pub mute: bool,
+        pub rumble: bool,
+        pub start_paused: bool,
+        pub dump_on_exit: Option<String>,
+        /// A replay to play back instead of starting the program from the beginning
+        pub replay: Option<String>,
+        /// Instructions to run each ROM for with `TestDir` and `Headless`
+        pub cycles: u64,
+        /// Seconds to show each ROM for with `Attract`
+        pub attract_time: u64,
+        /// Per-ROM settings given on the command line
+        pub settings: Settings,
+    }
+
+    impl Config {
+        pub fn from_args(args: &[String]) -> Chip8Result<Config> {
+            let mut command = Command::Run;
+            let mut program = None;
+            let mut rom_format = None;
+            let mut turbo_key = DEFAULT_TURBO_KEY;
+            let mut slowmo_key = DEFAULT_SLOWMO_KEY;
+            let mut auto_save = AutoSave::Off;
+            let mut record_audio = None;
+            let mut mute = false;
+            let mut rumble = true;
+            let mut start_paused = false;
+            let mut dump_on_exit = None;
+            let mut replay = None;
+            let mut cycles = DEFAULT_CYCLES;
+            let mut attract_time = DEFAULT_ATTRACT_TIME;
+            let mut settings = Settings::default();
+
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--format" => {
+                        rom_format = Some(RomFormat::from_name(Config::value(arg, args.next())?)?);
+                    }
+                    "--speed" => {
+                        let speed = Config::value(arg, args.next())?.parse()?;
+                        if speed == 0 {
+                            return Err("Speed must be at least 1".into());
+                        }
+                        settings.speed = Some(speed);
+                    }
+                    "--turbo-key" => {
+                        turbo_key = settings::parse_keycode(Config::value(arg, args.next())?)?;
+                    }
+                    "--turbo-factor" => {
+                        let turbo_factor = Config::value(arg, args.next())?.parse()?;
+                        if turbo_factor == 0 {
+                            return Err("Turbo factor must be at least 1".into());
+                        }
+                        settings.turbo_factor = Some(turbo_factor);
+                    }
+                    "--slowmo-key" => {
+                        slowmo_key = settings::parse_keycode(Config::value(arg, args.next())?)?;
+                    }
+                    "--slowmo-factor" => {
+                        let slowmo_factor: f64 = Config::value(arg, args.next())?.parse()?;
+                        if !(slowmo_factor > 0.0 && slowmo_factor <= 1.0) {
+                            return Err("Slow motion factor must be greater than 0 and at most 1".into());
+                        }
+                        settings.slowmo_factor = Some(slowmo_factor);
+                    }
+                    "--run-ahead" => {
+                        let run_ahead = Config::value(arg, args.next())?.parse()?;
+                        if run_ahead > MAX_RUN_AHEAD {
+                            return Err(format!("Run-ahead can be at most {} frames", MAX_RUN_AHEAD).into());
+                        }
+                        settings.run_ahead = Some(run_ahead);
+                    }
+                    "--foreground" => {
+                        let color = Config::value(arg, args.next())?;
+                        settings::parse_color(color)?;
+                        settings.foreground = Some(color.to_string());
+                    }
+                    "--background" => {
+                        let color = Config::value(arg, args.next())?;
+                        settings::parse_color(color)?;
+                        settings.background = Some(color.to_string());
+                    }
+                    "--key" => {
+                        let binding = Config::value(arg, args.next())?;
+                        let (host, chip8) = binding.rsplit_once('=')
+                            .ok_or_else(|| format!("Invalid key binding: {}, expected <KEY>=<0-F>", binding))?;
+                        settings::parse_keycode(host)?;
+                        settings::parse_key(chip8)?;
+                        settings.keymap.insert(host.to_string(), chip8.to_uppercase());
+                    }
+                    "--macro" => {
+                        let binding = Config::value(arg, args.next())?;
+                        let (host, steps) = binding.rsplit_once('=')
+                            .ok_or_else(|| format!("Invalid macro binding: {}, expected <KEY>=<STEPS>", binding))?;
+                        settings::parse_keycode(host)?;
+                        settings::parse_macro(steps)?;
+                        settings.macros.insert(host.to_string(), steps.to_uppercase());
+                    }
+                    "--autofire" => {
+                        let binding = Config::value(arg, args.next())?;
+                        let invalid = || format!("Invalid autofire binding: {}, expected <KEY>=<1-{}>", binding, MAX_AUTOFIRE_RATE);
+                        let (host, rate) = binding.rsplit_once('=').ok_or_else(invalid)?;
+                        settings::parse_keycode(host)?;
+                        let rate = rate.parse::<u32>().ok()
+                            .filter(|rate| (1..=MAX_AUTOFIRE_RATE).contains(rate))
+                            .ok_or_else(invalid)?;
+                        settings.autofire.insert(host.to_string(), rate);
+                    }
+                    "--invert" => settings.invert = Some(true),
+                    "--lcd" => settings.lcd = Some(true),
+                    "--display" => {
+                        let size = Config::value(arg, args.next())?;
+                        settings::parse_display_size(size)?;
+                        settings.display = Some(size.to_string());
+                    }
+                    "--platform" => {
+                        let name = Config::value(arg, args.next())?;
+                        Platform::from_name(name)?;
+                        settings.platform = Some(name.to_string());
+                    }
+                    "--quirk" => {
+                        let setting = Config::value(arg, args.next())?;
+                        let invalid = || format!("Invalid quirk setting: {}, expected <NAME>=<on|off>", setting);
+                        let (name, value) = setting.split_once('=').ok_or_else(invalid)?;
+                        let value = match value {
+                            "on" => true,
+                            "off" => false,
+                            _ => return Err(invalid().into()),
+                        };
+                        Platform::Chip8.quirks().get(name)?;
+                        settings.quirks.insert(name.to_string(), value);
+                    }
+                    "--auto-save" => {
+                        auto_save = match Config::value(arg, args.next())? {
+                            "off" => AutoSave::Off,
+                            "ask" => AutoSave::Ask,
+                            "resume" => AutoSave::Resume,
+                            mode => return Err(format!("Unknown auto-save mode: {}", mode).into()),
+                        };
+                    }
+                    "--record-audio" => {
+                        record_audio = Some(Config::value(arg, args.next())?.to_string());
+                    }
+                    "--mute" => mute = true,
+                    "--no-rumble" => rumble = false,
+                    "--list-platforms" => command = Command::ListPlatforms,
+                    "--list-quirks" => command = Command::ListQuirks,
+                    "--headless" => command = Command::Headless,
+                    "test-dir" if command == Command::Run && program.is_none() => command = Command::TestDir,
+                    "lint" if command == Command::Run && program.is_none() => command = Command::Lint,
+                    "stats" if command == Command::Run && program.is_none() => command = Command::Stats,
+                    "attract" if command == Command::Run && program.is_none() => command = Command::Attract,
+                    "--cycles" => cycles = Config::value(arg, args.next())?.parse()?,
+                    "--attract-time" => {
+                        attract_time = Config::value(arg, args.next())?.parse()?;
+                        if attract_time == 0 {
+                            return Err("Attract time must be at least 1 second".into());
+                        }
+                    }
+                    "--start-paused" => start_paused = true,
+                    "--dump-on-exit" => {
+                        dump_on_exit = Some(Config::value(arg, args.next())?.to_string());
+                    }
+                    "--replay" => {
+                        replay = Some(Config::value(arg, args.next())?.to_string());
+                    }
+                    _ if arg.starts_with("--") => {
+                        return Err(format!("Unknown option: {}", arg).into());
+                    }
+                    _ if program.is_none() => program = Some(arg.clone()),
+                    _ => return Err(format!("Unexpected argument: {}", arg).into()),
+                }
+            }
+
+            Ok(Config {
+                command,
+                program,
+                rom_format,
+                turbo_key,
+                slowmo_key,
+                auto_save,
+                record_audio,
+                mute,
+                rumble,
+                start_paused,
+                dump_on_exit,
+                replay,
+                cycles,
+                attract_time,
+                settings,
+            })
+        }
+
+        fn value<'a>(option: &str, value: Option<&'a String>) -> Chip8Result<&'a str> {
+            match value {
+                Some(value) => Ok(value),
+                None => Err(format!("Missing value for {}", option).into()),
+            }
+        }
+    }
+}
@@ -1,14 +1,21 @@
 pub use cpu::CPU;
+pub use cpu::LOAD_ADDRESS;
+pub use cpu::MEMORY_SIZE;
+pub use cpu::MAX_STACK_DEPTH;
+pub use cpu::Event;
+pub use cpu::CpuError;
+pub use cpu::Fault;
 
 pub mod cpu {
-    use std::{fs::File, io::Read, convert::TryInto};
+    use std::{error::Error, fmt, fs::File, io::Read};
     use rand::Rng;
-    use crate::emulator::{Chip8Result, input::Keyboard, ui};
+    use serde::{Deserialize, Serialize};
+    use crate::emulator::{Chip8Error, Chip8Result, engine::Chip8Core, hooks::Hooks, input::Key, instruction::{self, Instruction}, quirks::Quirks, ui, variants::{ForeignOpcode, Platform}};
 
-    const LOAD_ADDRESS: usize = 0x200;
-    const MEMORY_SIZE: usize = 4096;
+    pub const LOAD_ADDRESS: usize = 0x200;
+    pub const MEMORY_SIZE: usize = 4096;
     const MAX_PROGRAM_SIZE: usize = MEMORY_SIZE - LOAD_ADDRESS;
-    const MAX_STACK_DEPTH: usize = 16;
+    pub const MAX_STACK_DEPTH: usize = 16;
 
     const SPRITE_SIZE: u16 = 5;
     const SPRITES: [u8; 80] = [
@@ -30,60 +37,141 @@ pub mod cpu {
         0xf0, 0x80, 0xf0, 0x80, 0x80,
     ];
 
+    /// The interpreter's state. Serializing it captures the whole machine, including the
+    /// display and the quirks in use, but not the platform, the keys being held or the hooks.
+    #[derive(Serialize, Deserialize)]
     pub struct CPU {
         pc: usize,
+        #[serde(with = "memory")]
         memory: [u8; MEMORY_SIZE],
+        /// The program as it was loaded, which memory is restored from on reset
+        rom: Vec<u8>,
         registers: [u8; 16],
         i_register: u16,
         delay_register: u8,
         sound_register: u8,
         stack_pointer: usize,
         stack: [u16; MAX_STACK_DEPTH],
+        #[serde(skip, default = "default_platform")]
+        platform: Platform,
+        quirks: Quirks,
+        /// Instructions decoded so far, indexed by address
+        #[serde(skip, default = "empty_cache")]
+        decoded: Vec<Option<Instruction>>,
+        display: ui::Buffer,
+        /// State of the xorshift generator CXNN draws from. It's part of the machine so that
+        /// a program replayed from a saved state gets the same random numbers.
+        random: u64,
+        /// Which of the 16 keys are held down, indexed by their CHIP-8 code
+        #[serde(skip)]
+        keys: [bool; 16],
+        #[serde(skip)]
+        hooks: Hooks,
+    }
+
+    /// Side effects of running the CPU that a frontend has to act on.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Event {
+        /// The display was changed and should be redrawn
+        DrawRequested,
+        SoundStarted,
+        SoundStopped,
+        /// FX0A is waiting for a key to be pressed
+        WaitingForKey,
+        /// The program jumped to itself, which is how most programs end
+        Halted,
+    }
+
+    /// Cloning copies the machine, its platform, quirks and the keys being held, but not the
+    /// hooks, which can't be cloned.
+    impl Clone for CPU {
+        fn clone(&self) -> CPU {
+            let mut cpu = CPU::new(Vec::new());
+            cpu.load_machine_state(self);
+            cpu.platform = self.platform;
+            cpu.quirks = self.quirks;
+            cpu.keys = self.keys;
+            cpu
+        }
     }
 
     impl CPU {
         pub fn from_file(filename: &str) -> Chip8Result<CPU> {
-            let mut file = File::open(filename)?;
-            let mut buffer = vec![0; LOAD_ADDRESS];
-            for (i, byte) in SPRITES.iter().enumerate() {
-                buffer[i] = *byte;
-            }
-            file.read_to_end(&mut buffer)?;
-
-            if buffer.len() == LOAD_ADDRESS {
-                Err(format!("{} is empty", filename).into())
-            } else if buffer.len() > MEMORY_SIZE {
-                Err(format!("Tried to load {} bytes, which is more than the allowed maximum of {} bytes",
-                    buffer.len(), MAX_PROGRAM_SIZE).into())
-            } else {
-                for _ in buffer.len()..MEMORY_SIZE {
-                    buffer.push(0);
-                }
-                Ok(CPU::new(buffer.try_into().unwrap()))
+            File::open(filename).map_err(Chip8Error::from)
+                .and_then(CPU::from_reader)
+                .map_err(|e| format!("Could not load {}: {}", filename, e).into())
+        }
+
+        pub fn from_reader(mut reader: impl Read) -> Chip8Result<CPU> {
+            let mut program = Vec::new();
+            reader.read_to_end(&mut program)?;
+            CPU::from_bytes(&program)
+        }
+
+        /// Creates a CPU with `program` loaded at `LOAD_ADDRESS` and the font at the start of memory.
+        pub fn from_bytes(program: &[u8]) -> Chip8Result<CPU> {
+            if program.is_empty() {
+                return Err("The program is empty".into());
+            }
+            if program.len() > MAX_PROGRAM_SIZE {
+                return Err(format!("Tried to load {} bytes, which is more than the allowed maximum of {} bytes",
+                    program.len(), MAX_PROGRAM_SIZE).into());
             }
+
+            let mut cpu = CPU::new(program.to_vec());
+            cpu.reset();
+            Ok(cpu)
         }
 
-        fn new(memory: [u8; MEMORY_SIZE]) -> CPU {
+        fn new(rom: Vec<u8>) -> CPU {
             CPU {
                 pc: LOAD_ADDRESS,
-                memory,
+                memory: [0; MEMORY_SIZE],
+                rom,
                 registers: [0; 16],
                 i_register: 0,
                 delay_register: 0,
                 sound_register: 0,
                 stack_pointer: 0,
                 stack: [0; MAX_STACK_DEPTH],
+                platform: Platform::Chip8,
+                quirks: Platform::Chip8.quirks(),
+                decoded: empty_cache(),
+                display: ui::Buffer::new(),
+                // Xorshift gets stuck at zero, so it must never be seeded with it
+                random: rand::thread_rng().gen::<u64>() | 1,
+                keys: [false; 16],
+                hooks: Hooks::default(),
             }
         }
 
-        fn jump(&mut self, addr: usize) -> Chip8Result<()> {
+        /// Sets the platform the program is run as. Only CHIP-8 instructions are executed
+        /// whatever it is, but its quirks are usually the ones in use.
+        pub fn set_platform(&mut self, platform: Platform) {
+            self.platform = platform;
+        }
+
+        /// Switches to a blank display `height` rows high, which must be one of `ui::HEIGHTS`.
+        pub fn set_display_height(&mut self, height: usize) {
+            self.display = ui::Buffer::with_height(height);
+        }
+
+        fn is_key_pressed(&self, code: u8) -> bool {
+            self.keys.get(code as usize).cloned().unwrap_or(false)
+        }
+
+        fn any_pressed_key(&self) -> Option<u8> {
+            self.keys.iter().position(|pressed| *pressed).map(|code| code as u8)
+        }
+
+        fn jump(&mut self, addr: usize) -> Result<(), Fault> {
             self.pc = addr;
             Ok(())
         }
 
-        fn call(&mut self, addr: usize) -> Chip8Result<()> {
+        fn call(&mut self, addr: usize) -> Result<(), Fault> {
             if self.stack_pointer >= MAX_STACK_DEPTH {
-                return Err("Stack overflow".into());
+                return Err(Fault::StackOverflow);
             }
 
             self.stack[self.stack_pointer] = self.pc as u16 + 2;
@@ -92,9 +180,9 @@ pub mod cpu {
             self.jump(addr)
         }
 
-        fn ret(&mut self) -> Chip8Result<()> {
+        fn ret(&mut self) -> Result<(), Fault> {
             if self.stack_pointer == 0 {
-                return Err("Attempting return but call stack is empty".into());
+                return Err(Fault::StackUnderflow);
             }
 
             self.stack_pointer -= 1;
@@ -105,7 +193,7 @@ pub mod cpu {
             self.pc += 2;
         }
 
-        fn skip_reg_imm_eq(&mut self, register: u8, byte: u8) -> Chip8Result<()> {
+        fn skip_reg_imm_eq(&mut self, register: u8, byte: u8) -> Result<(), Fault> {
             if self.registers[register as usize] == byte {
                 self.increment_pc();
             }
@@ -113,7 +201,7 @@ pub mod cpu {
             Ok(())
         }
 
-        fn skip_reg_imm_neq(&mut self, register: u8, byte: u8) -> Chip8Result<()> {
+        fn skip_reg_imm_neq(&mut self, register: u8, byte: u8) -> Result<(), Fault> {
             if self.registers[register as usize] != byte {
                 self.increment_pc();
             }
@@ -121,7 +209,7 @@ pub mod cpu {
             Ok(())
         }
 
-        fn skip_reg_reg_eq(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn skip_reg_reg_eq(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             if self.registers[register1 as usize] == self.registers[register2 as usize] {
                 self.increment_pc();
             }
@@ -129,7 +217,7 @@ pub mod cpu {
             Ok(())
         }
 
-        fn skip_reg_reg_neq(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn skip_reg_reg_neq(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             if self.registers[register1 as usize] != self.registers[register2 as usize] {
                 self.increment_pc();
             }
@@ -137,44 +225,47 @@ pub mod cpu {
             Ok(())
         }
 
-        fn load_imm(&mut self, register: u8, byte: u8) -> Chip8Result<()> {
+        fn load_imm(&mut self, register: u8, byte: u8) -> Result<(), Fault> {
             self.registers[register as usize] = byte;
             self.increment_pc();
             Ok(())
         }
 
-        fn add_imm(&mut self, register: u8, byte: u8) -> Chip8Result<()> {
+        fn add_imm(&mut self, register: u8, byte: u8) -> Result<(), Fault> {
             let (result, _) = byte.overflowing_add(self.registers[register as usize]);
             self.registers[register as usize] = result;
             self.increment_pc();
             Ok(())
         }
 
-        fn load_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn load_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.registers[register1 as usize] = self.registers[register2 as usize];
             self.increment_pc();
             Ok(())
         }
 
-        fn or_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn or_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.registers[register1 as usize] |= self.registers[register2 as usize];
+            if self.quirks.vf_reset { self.registers[0xf] = 0; }
             self.increment_pc();
             Ok(())
         }
 
-        fn and_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn and_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.registers[register1 as usize] &= self.registers[register2 as usize];
+            if self.quirks.vf_reset { self.registers[0xf] = 0; }
             self.increment_pc();
             Ok(())
         }
 
-        fn xor_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn xor_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.registers[register1 as usize] ^= self.registers[register2 as usize];
+            if self.quirks.vf_reset { self.registers[0xf] = 0; }
             self.increment_pc();
             Ok(())
         }
 
-        fn add_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn add_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             let (result, overflow) =
                 self.registers[register1 as usize].overflowing_add(self.registers[register2 as usize]);
             self.registers[register1 as usize] = result;
@@ -183,7 +274,7 @@ pub mod cpu {
             Ok(())
         }
 
-        fn sub_reg_with_dest(&mut self, register1: u8, register2: u8, dest: u8) -> Chip8Result<()> {
+        fn sub_reg_with_dest(&mut self, register1: u8, register2: u8, dest: u8) -> Result<(), Fault> {
             let (result, overflow) =
                 self.registers[register1 as usize].overflowing_sub(self.registers[register2 as usize]);
             self.registers[dest as usize] = result;
@@ -192,106 +283,98 @@ pub mod cpu {
             Ok(())
         }
 
-        fn sub_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn sub_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.sub_reg_with_dest(register1, register2, register1)
         }
 
-        fn subn_reg(&mut self, register1: u8, register2: u8) -> Chip8Result<()> {
+        fn subn_reg(&mut self, register1: u8, register2: u8) -> Result<(), Fault> {
             self.sub_reg_with_dest(register2, register1, register1)
         }
 
-        fn shr_reg(&mut self, register: u8) -> Chip8Result<()> {
+        fn shr_reg(&mut self, register: u8, source: u8) -> Result<(), Fault> {
+            if self.quirks.shift_vy {
+                self.registers[register as usize] = self.registers[source as usize];
+            }
             self.registers[0xf] = self.registers[register as usize] & 0x1;
             self.registers[register as usize] >>= 1;
             self.increment_pc();
             Ok(())
         }
 
-        fn shl_reg(&mut self, register: u8) -> Chip8Result<()> {
+        fn shl_reg(&mut self, register: u8, source: u8) -> Result<(), Fault> {
+            if self.quirks.shift_vy {
+                self.registers[register as usize] = self.registers[source as usize];
+            }
             self.registers[0xf] = self.registers[register as usize] >> 7;
             self.registers[register as usize] <<= 1;
             self.increment_pc();
             Ok(())
         }
 
-        fn set_i(&mut self, addr: u16) -> Chip8Result<()> {
+        fn set_i(&mut self, addr: u16) -> Result<(), Fault> {
             self.i_register = addr;
             self.increment_pc();
             Ok(())
         }
 
-        fn jump_v0(&mut self, addr: usize) -> Chip8Result<()> {
-            self.jump(self.registers[0] as usize + addr)
+        fn jump_v0(&mut self, addr: usize) -> Result<(), Fault> {
+            let register = if self.quirks.jump_vx { addr >> 8 } else { 0 };
+            self.jump(self.registers[register] as usize + addr)
         }
 
-        fn load_and_rnd_imm(&mut self, register: u8, byte: u8) -> Chip8Result<()> {
-            let random_byte = rand::thread_rng().gen_range(0..=255);
-            self.registers[register as usize] = random_byte & byte;
+        fn load_and_rnd_imm(&mut self, register: u8, byte: u8) -> Result<(), Fault> {
+            self.random ^= self.random << 13;
+            self.random ^= self.random >> 7;
+            self.random ^= self.random << 17;
+            self.registers[register as usize] = (self.random >> 32) as u8 & byte;
             self.increment_pc();
             Ok(())
         }
 
-        fn draw_byte(&mut self, x: usize, y: usize, mut byte: u8, display_buffer: &mut ui::DisplayBuffer) -> bool {
-            let mut collided = false;
-            byte = byte.reverse_bits();
-            for bit in 0..8 {
-                let i = y % ui::HEIGHT as usize;
-                let j = (x + bit) % ui::WIDTH as usize;
-                let prev = display_buffer.buffer[i][j];
-
-                display_buffer.buffer[i][j] = (prev as u8 ^ (byte & 1)) != 0;
-
-                if prev && !display_buffer.buffer[i][j] {
-                    collided = true;
-                }
-
-                byte >>= 1;
-            }
-
-            collided
-        }
-
-        fn draw_sprite(&mut self, register1: u8, register2: u8, bytes: u8, display_buffer: &mut ui::DisplayBuffer) -> Chip8Result<()> {
+        fn draw_sprite(&mut self, register1: u8, register2: u8, bytes: u8) -> Result<(), Fault> {
             if self.i_register as usize + bytes as usize > MEMORY_SIZE {
-                return Err(
-                    format!(
-                        "Cannot read {} byte sprite starting from 0x{:0x}", bytes, self.i_register
-                    ).into()
-                );
+                return Err(Fault::MemoryOutOfBounds { addr: self.i_register as usize, len: bytes as usize });
             }
 
-            let x = self.registers[register1 as usize] as usize;
-            let mut y = self.registers[register2 as usize] as usize;
+            let x = self.registers[register1 as usize] as usize % ui::WIDTH as usize;
+            let y = self.registers[register2 as usize] as usize;
             let i = self.i_register as usize;
-
-            let bytes: Vec<u8> = self.memory[i..i + bytes as usize].iter().cloned().collect();
+            let height = self.display.height();
 
             let mut collided = false;
 
-            for byte in bytes {
-                if self.draw_byte(x, y, byte, display_buffer) {
-                    collided = true;
+            // Each sprite row is shifted into place as a whole. When clipping, only the starting
+            // position wraps around and whatever is past the edges is dropped.
+            for (row, byte) in self.memory[i..i + bytes as usize].iter().enumerate() {
+                let bits = (*byte as u64) << (ui::WIDTH - 8);
+                if self.quirks.clip_sprites {
+                    let y = y % height + row;
+                    if y >= height {
+                        break;
+                    }
+                    collided |= self.display.xor_row(y, bits >> x);
+                } else {
+                    let y = (y + row) % height;
+                    collided |= self.display.xor_row(y, bits.rotate_right(x as u32));
                 }
-                y += 1;
             }
 
             self.registers[0xf] = collided as u8;
-            display_buffer.is_dirty = true;
             self.increment_pc();
 
             Ok(())
         }
 
-        fn skip_keydown(&mut self, register: u8, keyboard: &Keyboard) -> Chip8Result<()> {
-            if keyboard.is_key_pressed(self.registers[register as usize]) {
+        fn skip_keydown(&mut self, register: u8) -> Result<(), Fault> {
+            if self.is_key_pressed(self.registers[register as usize]) {
                 self.increment_pc();
             }
             self.increment_pc();
             Ok(())
         }
 
-        fn skip_not_keydown(&mut self, register: u8, keyboard: &Keyboard) -> Chip8Result<()> {
-            if !keyboard.is_key_pressed(self.registers[register as usize]) {
+        fn skip_not_keydown(&mut self, register: u8) -> Result<(), Fault> {
+            if !self.is_key_pressed(self.registers[register as usize]) {
                 self.increment_pc();
             }
             self.increment_pc();
@@ -299,51 +382,52 @@ pub mod cpu {
         }
 
 
-        fn load_delay_timer(&mut self, register: u8) -> Chip8Result<()> {
+        fn load_delay_timer(&mut self, register: u8) -> Result<(), Fault> {
             self.registers[register as usize] = self.delay_register;
             self.increment_pc();
             Ok(())
         }
 
-        fn set_delay_timer(&mut self, register: u8) -> Chip8Result<()> {
+        fn set_delay_timer_reg(&mut self, register: u8) -> Result<(), Fault> {
             self.delay_register = self.registers[register as usize];
             self.increment_pc();
             Ok(())
         }
 
-        fn set_sound_timer(&mut self, register: u8) -> Chip8Result<()> {
+        fn set_sound_timer_reg(&mut self, register: u8) -> Result<(), Fault> {
             self.sound_register = self.registers[register as usize];
             self.increment_pc();
             Ok(())
         }
 
-        fn wait_keypress(&mut self, register: u8, keyboard: &Keyboard) -> Chip8Result<()> {
-            if let Some(code) = keyboard.any_pressed_key() {
+        fn wait_keypress(&mut self, register: u8) -> Result<(), Fault> {
+            if let Some(code) = self.any_pressed_key() {
                 self.registers[register as usize] = code;
                 self.increment_pc();
+            } else if let Some(mut hook) = self.hooks.on_key_wait.take() {
+                hook(self, register);
+                self.hooks.on_key_wait = Some(hook);
             }
             Ok(())
         }
 
-        fn add_i_reg(&mut self, register: u8) -> Chip8Result<()> {
+        fn add_i_reg(&mut self, register: u8) -> Result<(), Fault> {
             self.i_register = self.i_register.overflowing_add(self.registers[register as usize] as u16).0;
             self.increment_pc();
             Ok(())
         }
 
-        fn load_sprite_address(&mut self, register: u8) -> Chip8Result<()> {
+        fn load_sprite_address(&mut self, register: u8) -> Result<(), Fault> {
             let digit = self.registers[register as usize] as u16;
             self.i_register = digit * SPRITE_SIZE;
             self.increment_pc();
             Ok(())
         }
 
-        fn store_bcd_representation(&mut self, register: u8) -> Chip8Result<()> {
+        fn store_bcd_representation(&mut self, register: u8) -> Result<(), Fault> {
             let i = self.i_register as usize;
             if i >= MEMORY_SIZE - 2 {
-                return Err(
-                    format!("Cannot store BCD representation at address 0x{:0x}", i).into()
-                );
+                return Err(Fault::MemoryOutOfBounds { addr: i, len: 3 });
             }
             let mut value = self.registers[register as usize];
             self.memory[i + 2] = value % 10;
@@ -351,130 +435,436 @@ pub mod cpu {
             self.memory[i + 1] = value % 10;
             value /= 10;
             self.memory[i] = value % 10;
+            self.invalidate(i, 3);
 
             self.increment_pc();
             Ok(())
         }
 
-        fn store_registers(&mut self, last_register: u8) -> Chip8Result<()> {
+        fn store_registers(&mut self, last_register: u8) -> Result<(), Fault> {
             let i = self.i_register as usize;
             let last_register = last_register as usize;
 
             if i + last_register >= MEMORY_SIZE {
-                return Err(
-                    format!("Cannot store {} registers starting at address 0x{:0x}",
-                        last_register + 1, i).into()
-                );
+                return Err(Fault::MemoryOutOfBounds { addr: i, len: last_register + 1 });
             }
 
             for r in 0..=last_register {
                 self.memory[i + r] = self.registers[r];
             }
+            self.invalidate(i, last_register + 1);
+            if self.quirks.memory_increments_i {
+                self.i_register += last_register as u16 + 1;
+            }
 
             self.increment_pc();
             Ok(())
         }
 
-        fn read_registers(&mut self, last_register: u8) -> Chip8Result<()> {
+        fn read_registers(&mut self, last_register: u8) -> Result<(), Fault> {
             let i = self.i_register as usize;
             let last_register = last_register as usize;
 
             if i + last_register >= MEMORY_SIZE {
-                return Err(
-                    format!("Cannot read {} registers starting from address 0x{:0x}",
-                        last_register + 1, i).into()
-                );
+                return Err(Fault::MemoryOutOfBounds { addr: i, len: last_register + 1 });
             }
 
             for r in 0..=last_register {
                 self.registers[r] = self.memory[i + r];
             }
+            if self.quirks.memory_increments_i {
+                self.i_register += last_register as u16 + 1;
+            }
 
             self.increment_pc();
             Ok(())
         }
 
-        fn clear_screen(&mut self, display_buffer: &mut ui::DisplayBuffer) -> Chip8Result<()> {
-            for i in 0..display_buffer.buffer.len() {
-                for j in 0..display_buffer.buffer[i].len() {
-                    display_buffer.buffer[i][j] = false;
-                }
-            }
-            display_buffer.is_dirty = true;
+        fn clear_screen(&mut self) -> Result<(), Fault> {
+            self.display.clear();
             self.increment_pc();
             Ok(())
         }
 
-        fn noop(&mut self) ->Chip8Result<()> {
+        fn noop(&mut self) -> Result<(), Fault> {
             self.increment_pc();
             Ok(())
         }
 
-        pub fn tick(&mut self, keyboard: &Keyboard, display_buffer: &mut ui::DisplayBuffer, decrement_timers: bool) -> Chip8Result<()> {
+        /// Wraps a fault with the state of the machine when it happened, with the PC still
+        /// pointing at the instruction that caused it.
+        fn error(&self, fault: Fault, pc: usize) -> CpuError {
+            let opcode = self.opcode_at(pc);
+            CpuError {
+                fault,
+                pc,
+                opcode,
+                instruction: instruction::decode(opcode),
+                registers: self.registers,
+                i_register: self.i_register,
+            }
+        }
+
+        /// Returns the instruction at the PC, decoding it only if it hasn't been seen
+        /// since the memory it occupies was last written.
+        fn fetch(&mut self) -> Instruction {
+            match self.decoded[self.pc] {
+                Some(instruction) => instruction,
+                None => {
+                    let opcode = ((self.memory[self.pc] as u16) << 8) | self.memory[self.pc + 1] as u16;
+                    let instruction = instruction::decode(opcode);
+                    self.decoded[self.pc] = Some(instruction);
+                    instruction
+                }
+            }
+        }
+
+        /// Drops decoded instructions overlapping `len` bytes of memory starting from `addr`,
+        /// which must be called whenever memory is written.
+        fn invalidate(&mut self, addr: usize, len: usize) {
+            let start = addr.saturating_sub(1);
+            let end = (addr + len).min(MEMORY_SIZE);
+            for decoded in self.decoded[start..end].iter_mut() {
+                *decoded = None;
+            }
+        }
+    }
+
+    impl Chip8Core for CPU {
+        fn platform(&self) -> Platform {
+            self.platform
+        }
+
+        fn hooks_mut(&mut self) -> &mut Hooks {
+            &mut self.hooks
+        }
+
+        /// Keeps the display height and the configuration.
+        fn load(&mut self, program: &[u8]) -> Chip8Result<()> {
+            let height = self.display.height();
+            self.load_machine_state(&CPU::from_bytes(program)?);
+            self.set_display_height(height);
+            Ok(())
+        }
+
+        /// Puts the machine back in the state it was in right after the program was loaded,
+        /// keeping the quirks, hooks and keys.
+        fn reset(&mut self) {
+            self.pc = LOAD_ADDRESS;
+            self.memory = [0; MEMORY_SIZE];
+            self.memory[..SPRITES.len()].copy_from_slice(&SPRITES);
+            self.memory[LOAD_ADDRESS..LOAD_ADDRESS + self.rom.len()].copy_from_slice(&self.rom);
+            self.registers = [0; 16];
+            self.i_register = 0;
+            self.delay_register = 0;
+            self.sound_register = 0;
+            self.stack_pointer = 0;
+            self.stack = [0; MAX_STACK_DEPTH];
+            self.decoded = empty_cache();
+            self.display.clear();
+        }
+
+        /// Copies the machine state (memory, registers, timers, stack and display) from `other`,
+        /// keeping this CPU's quirks, hooks and keys.
+        fn load_machine_state(&mut self, other: &CPU) {
+            self.pc = other.pc;
+            self.memory = other.memory;
+            self.rom.clone_from(&other.rom);
+            self.registers = other.registers;
+            self.i_register = other.i_register;
+            self.delay_register = other.delay_register;
+            self.sound_register = other.sound_register;
+            self.stack_pointer = other.stack_pointer;
+            self.stack = other.stack;
+            self.decoded.clone_from(&other.decoded);
+            self.display = other.display;
+            self.random = other.random;
+        }
+
+        fn validate(&self) -> Chip8Result<()> {
+            if self.stack_pointer > MAX_STACK_DEPTH {
+                return Err(format!("Invalid stack pointer: {}", self.stack_pointer).into());
+            }
             if self.pc + 1 >= MEMORY_SIZE {
-                return Err(
-                    format!("PC out of bounds: 0x{:0x}", self.pc).into()
-                )
-            }
-
-            if decrement_timers {
-                if self.delay_register > 0 { self.delay_register -= 1; }
-                if self.sound_register > 0 { self.sound_register -= 1; }
-            }
-
-            let high = self.memory[self.pc];
-            let low = self.memory[self.pc + 1];
-            let a = (high & 0xf0) >> 4;
-            let x = high & 0xf;
-            let y = (low & 0xf0) >> 4;
-            let b = low & 0xf;
-            let nnn = (((high as usize) & 0xf) << 8) | low as usize;
-
-            match (a, x, y, b) {
-                (0, 0, 0xe, 0) => self.clear_screen(display_buffer),
-                (0, 0, 0xe, 0xe) => self.ret(),
-                (0, _, _, _) => self.noop(),
-                (1, _, _, _) => self.jump(nnn),
-                (2, _, _, _) => self.call(nnn),
-                (3, _, _, _) => self.skip_reg_imm_eq(x, low),
-                (4, _, _, _) => self.skip_reg_imm_neq(x, low),
-                (5, _, _, 0) => self.skip_reg_reg_eq(x, y),
-                (6, _, _, _) => self.load_imm(x, low),
-                (7, _, _, _) => self.add_imm(x, low),
-                (8, _, _, 0) => self.load_reg(x, y),
-                (8, _, _, 1) => self.or_reg(x, y),
-                (8, _, _, 2) => self.and_reg(x, y),
-                (8, _, _, 3) => self.xor_reg(x, y),
-                (8, _, _, 4) => self.add_reg(x, y),
-                (8, _, _, 5) => self.sub_reg(x, y),
-                (8, _, _, 6) => self.shr_reg(x),
-                (8, _, _, 7) => self.subn_reg(x, y),
-                (8, _, _, 0xe) => self.shl_reg(x),
-                (9, _, _, 0) => self.skip_reg_reg_neq(x, y),
-                (0xa, _, _, _) => self.set_i(nnn as u16),
-                (0xb, _, _, _) => self.jump_v0(nnn),
-                (0xc, _, _, _) => self.load_and_rnd_imm(x, low),
-                (0xd, _, _, _) => self.draw_sprite(x, y, b, display_buffer),
-                (0xe, _, 9, 0xe) => self.skip_keydown(x, keyboard),
-                (0xe, _, 0xa, 1) => self.skip_not_keydown(x, keyboard),
-                (0xf, _, 0, 7) => self.load_delay_timer(x),
-                (0xf, _, 0, 0xa) => self.wait_keypress(x, keyboard),
-                (0xf, _, 1, 5) => self.set_delay_timer(x),
-                (0xf, _, 1, 8) => self.set_sound_timer(x),
-                (0xf, _, 1, 0xe) => self.add_i_reg(x),
-                (0xf, _, 2, 9) => self.load_sprite_address(x),
-                (0xf, _, 3, 3) => self.store_bcd_representation(x),
-                (0xf, _, 5, 5) => self.store_registers(x),
-                (0xf, _, 6, 5) => self.read_registers(x),
-                _ => Err(
-                    format!(
-                        "Invalid instruction: 0x{:0x}, PC=0x{:0x}\n\t{:0x} {:0x} {:0x} {:0x}",
-                        ((high as u32) << 8) | low as u32,
-                        self.pc,
-                        a, x, y, b
-                    ).into()
-                )
+                return Err(format!("PC out of bounds: 0x{:0x}", self.pc).into());
+            }
+            if !ui::HEIGHTS.contains(&self.display.height()) {
+                return Err(format!("Unsupported display height: {}", self.display.height()).into());
+            }
+            if self.rom.len() > MAX_PROGRAM_SIZE {
+                return Err(format!("Program is too large: {} bytes", self.rom.len()).into());
+            }
+            if self.random == 0 {
+                return Err("Invalid random number generator state".into());
+            }
+            Ok(())
+        }
+
+        fn quirks(&self) -> Quirks {
+            self.quirks
+        }
+
+        fn set_quirks(&mut self, quirks: Quirks) {
+            self.quirks = quirks;
+        }
+
+        fn pc(&self) -> usize {
+            self.pc
+        }
+
+        fn set_pc(&mut self, pc: usize) -> Chip8Result<()> {
+            if pc + 1 >= MEMORY_SIZE {
+                return Err(format!("PC out of bounds: 0x{:0x}", pc).into());
             }
+            self.pc = pc;
+            Ok(())
+        }
+
+        fn registers(&self) -> &[u8; 16] {
+            &self.registers
+        }
+
+        fn set_register(&mut self, x: u8, value: u8) {
+            self.registers[x as usize] = value;
+        }
+
+        fn i_register(&self) -> u16 {
+            self.i_register
+        }
+
+        fn set_i_register(&mut self, value: u16) {
+            self.i_register = value;
+        }
+
+        fn delay_timer(&self) -> u8 {
+            self.delay_register
+        }
+
+        fn set_delay_timer(&mut self, value: u8) {
+            self.delay_register = value;
+        }
+
+        fn sound_timer(&self) -> u8 {
+            self.sound_register
+        }
+
+        fn set_sound_timer(&mut self, value: u8) {
+            self.sound_register = value;
+        }
+
+        fn stack_pointer(&self) -> usize {
+            self.stack_pointer
+        }
+
+        fn stack(&self) -> &[u16] {
+            &self.stack[..self.stack_pointer]
+        }
+
+        fn memory(&self) -> &[u8] {
+            &self.memory
+        }
+
+        fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Chip8Result<()> {
+            if addr + bytes.len() > MEMORY_SIZE {
+                return Err(format!("Cannot write {} bytes starting from 0x{:0x}", bytes.len(), addr).into());
+            }
+            self.memory[addr..addr + bytes.len()].copy_from_slice(bytes);
+            self.invalidate(addr, bytes.len());
+            Ok(())
+        }
+
+        /// Returns the two bytes at `addr` as an opcode, reading zeroes past the end of memory.
+        fn opcode_at(&self, addr: usize) -> u16 {
+            let byte = |addr: usize| self.memory.get(addr).cloned().unwrap_or(0) as u16;
+            (byte(addr) << 8) | byte(addr + 1)
+        }
+
+        /// Executes the instruction at the PC, returning the side effect it had, if any.
+        fn step(&mut self) -> Result<Option<Event>, CpuError> {
+            if self.pc + 1 >= MEMORY_SIZE {
+                return Err(self.error(Fault::PcOutOfBounds, self.pc));
+            }
+
+            let instruction = self.fetch();
+            if let Some(mut hook) = self.hooks.before_instruction.take() {
+                hook(self, instruction);
+                self.hooks.before_instruction = Some(hook);
+            }
+
+            let was_playing = self.is_sound_playing();
+            let pc = self.pc;
+            match instruction {
+                Instruction::ClearScreen => self.clear_screen(),
+                Instruction::Return => self.ret(),
+                Instruction::Sys(_) => self.noop(),
+                Instruction::Jump(nnn) => self.jump(nnn as usize),
+                Instruction::Call(nnn) => self.call(nnn as usize),
+                Instruction::SkipEqImm(x, nn) => self.skip_reg_imm_eq(x, nn),
+                Instruction::SkipNeqImm(x, nn) => self.skip_reg_imm_neq(x, nn),
+                Instruction::SkipEqReg(x, y) => self.skip_reg_reg_eq(x, y),
+                Instruction::LoadImm(x, nn) => self.load_imm(x, nn),
+                Instruction::AddImm(x, nn) => self.add_imm(x, nn),
+                Instruction::LoadReg(x, y) => self.load_reg(x, y),
+                Instruction::Or(x, y) => self.or_reg(x, y),
+                Instruction::And(x, y) => self.and_reg(x, y),
+                Instruction::Xor(x, y) => self.xor_reg(x, y),
+                Instruction::AddReg(x, y) => self.add_reg(x, y),
+                Instruction::Sub(x, y) => self.sub_reg(x, y),
+                Instruction::Shr(x, y) => self.shr_reg(x, y),
+                Instruction::SubN(x, y) => self.subn_reg(x, y),
+                Instruction::Shl(x, y) => self.shl_reg(x, y),
+                Instruction::SkipNeqReg(x, y) => self.skip_reg_reg_neq(x, y),
+                Instruction::SetI(nnn) => self.set_i(nnn),
+                Instruction::JumpOffset(nnn) => self.jump_v0(nnn as usize),
+                Instruction::Random(x, nn) => self.load_and_rnd_imm(x, nn),
+                Instruction::Draw(x, y, n) => self.draw_sprite(x, y, n),
+                Instruction::SkipKeyDown(x) => self.skip_keydown(x),
+                Instruction::SkipKeyUp(x) => self.skip_not_keydown(x),
+                Instruction::LoadDelay(x) => self.load_delay_timer(x),
+                Instruction::WaitKey(x) => self.wait_keypress(x),
+                Instruction::SetDelay(x) => self.set_delay_timer_reg(x),
+                Instruction::SetSound(x) => self.set_sound_timer_reg(x),
+                Instruction::AddI(x) => self.add_i_reg(x),
+                Instruction::LoadFont(x) => self.load_sprite_address(x),
+                Instruction::StoreBcd(x) => self.store_bcd_representation(x),
+                Instruction::StoreRegisters(x) => self.store_registers(x),
+                Instruction::ReadRegisters(x) => self.read_registers(x),
+                Instruction::Invalid(opcode) => Err(Fault::InvalidInstruction(ForeignOpcode::identify(opcode))),
+            }.map_err(|fault| self.error(fault, pc))?;
+
+            Ok(match instruction {
+                Instruction::Draw(..) | Instruction::ClearScreen => {
+                    if let Some(mut hook) = self.hooks.after_draw.take() {
+                        hook(self, &self.display);
+                        self.hooks.after_draw = Some(hook);
+                    }
+                    Some(Event::DrawRequested)
+                }
+                Instruction::WaitKey(_) if self.pc == pc => Some(Event::WaitingForKey),
+                Instruction::Jump(_) if self.pc == pc => Some(Event::Halted),
+                Instruction::SetSound(_) if !was_playing && self.is_sound_playing() => Some(Event::SoundStarted),
+                Instruction::SetSound(_) if was_playing && !self.is_sound_playing() => Some(Event::SoundStopped),
+                _ => None,
+            })
+        }
+
+        /// Counts the delay and sound timers down, which should happen at 60Hz.
+        fn tick_timers(&mut self) -> Option<Event> {
+            let was_playing = self.is_sound_playing();
+            if self.delay_register > 0 { self.delay_register -= 1; }
+            if self.sound_register > 0 { self.sound_register -= 1; }
+            if let Some(mut hook) = self.hooks.on_timer_tick.take() {
+                hook(self);
+                self.hooks.on_timer_tick = Some(hook);
+            }
+
+            if was_playing && !self.is_sound_playing() {
+                Some(Event::SoundStopped)
+            } else {
+                None
+            }
+        }
+
+        fn display(&self) -> &ui::Buffer {
+            &self.display
+        }
+
+        fn set_key(&mut self, key: Key, pressed: bool) {
+            self.keys[key.chip8_code() as usize] = pressed;
+        }
+
+        fn held_keys(&self) -> u16 {
+            self.keys.iter().enumerate().fold(0, |mask, (code, pressed)| mask | (*pressed as u16) << code)
+        }
+
+        fn is_sound_playing(&self) -> bool {
+            self.sound_register > 0
+        }
+    }
+
+    /// What went wrong when the CPU failed to execute an instruction.
+    #[derive(Clone, Copy, Debug)]
+    pub enum Fault {
+        PcOutOfBounds,
+        StackOverflow,
+        /// A return with an empty call stack
+        StackUnderflow,
+        /// The instruction accessed memory past its end
+        MemoryOutOfBounds { addr: usize, len: usize },
+        /// The opcode isn't a CHIP-8 instruction. If it belongs to one of CHIP-8's
+        /// extensions, that opcode is identified.
+        InvalidInstruction(Option<ForeignOpcode>),
+    }
+
+    impl fmt::Display for Fault {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Fault::PcOutOfBounds => write!(f, "PC out of bounds"),
+                Fault::StackOverflow => write!(f, "Stack overflow"),
+                Fault::StackUnderflow => write!(f, "Attempting return but call stack is empty"),
+                Fault::MemoryOutOfBounds { addr, len } =>
+                    write!(f, "Cannot access {} bytes of memory starting from 0x{:0x}", len, addr),
+                Fault::InvalidInstruction(Some(foreign)) => write!(f, "Invalid instruction: {}", foreign),
+                Fault::InvalidInstruction(None) => write!(f, "Invalid instruction"),
+            }
+        }
+    }
+
+    /// An error raised while executing an instruction, along with the state of the machine
+    /// at that point so it can be shown or inspected.
+    #[derive(Clone, Debug)]
+    pub struct CpuError {
+        pub fault: Fault,
+        pub pc: usize,
+        pub opcode: u16,
+        pub instruction: Instruction,
+        pub registers: [u8; 16],
+        pub i_register: u16,
+    }
+
+    impl fmt::Display for CpuError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "{}", self.fault)?;
+            writeln!(f, "\tat 0x{:03x}: {:04X} {}", self.pc, self.opcode, self.instruction)?;
+            write!(f, "\tI=0x{:03x}", self.i_register)?;
+            for (r, value) in self.registers.iter().enumerate() {
+                write!(f, "{}V{:X}={:02x}", if r % 8 == 0 { "\n\t" } else { " " }, r, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Error for CpuError {}
+
+    fn default_platform() -> Platform {
+        Platform::Chip8
+    }
+
+    fn empty_cache() -> Vec<Option<Instruction>> {
+        vec![None; MEMORY_SIZE]
+    }
+
+    /// Serde only implements its traits for arrays of up to 32 elements,
+    /// so memory is (de)serialized as a byte string.
+    mod memory {
+        use serde::{Deserialize, Deserializer, Serializer, de::Error};
+        use std::convert::TryInto;
+        use super::MEMORY_SIZE;
+
+        pub fn serialize<S: Serializer>(memory: &[u8; MEMORY_SIZE], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(memory)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; MEMORY_SIZE], D::Error> {
+            let bytes: Vec<u8> = Deserialize::deserialize(deserializer)?;
+            let len = bytes.len();
+            bytes.try_into().map_err(|_| D::Error::invalid_length(len, &"4096 bytes of memory"))
+        }
+    }
+
+    impl fmt::Display for CPU {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (self as &dyn Chip8Core).fmt(f)
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,341 @@
+pub use debugger::Debugger;
+pub use debugger::PAUSE_KEY;
+
+pub mod debugger {
+    use std::{collections::BTreeSet, fs, io::{self, BufRead, IsTerminal, Write}, path::PathBuf, sync::mpsc::{self, Receiver}, thread};
+    use sdl2::keyboard::Keycode;
+    use serde::{Deserialize, Serialize};
+
+    use crate::emulator::{Chip8Result, dump, engine::Chip8Core, expr, instruction::Instruction, storage};
+
+    pub const PAUSE_KEY: Keycode = Keycode::F12;
+
+    const HELP: &str = "Commands:
+    c, continue                  Resume execution
+    s, step [N]                  Execute N instructions (default: 1)
+    b, break <ADDR>              Set a breakpoint at a hex address
+    d, delete <ADDR>             Remove a breakpoint
+    l, list                      List breakpoints
+    r, regs                      Show the registers, highlighting the ones changed since the last stop
+    p, print <EXPR>              Evaluate an expression, e.g. print V[3] * 2 + I or print mem[I..I+5]
+    disp, display                Print the display as text
+    set <TARGET> = <EXPR>        Change a register or byte of memory, e.g. set V3 = 0x10 or set mem[I] = 0
+    bd, break-display [on|off]   Pause whenever an instruction modifies the display
+    bs, break-sound [on|off]     Pause whenever FX18 starts the sound timer
+    h, help                      Show this help";
+
+    /// A monitor that pauses execution and reads commands from the terminal, so the
+    /// window keeps running (and showing the display) while the program is inspected.
+    pub struct Debugger {
+        paused: bool,
+        steps: usize,
+        breakpoints: BTreeSet<usize>,
+        /// The breakpoint execution was resumed from, which mustn't pause it again right away
+        resumed_from: Option<usize>,
+        pub break_on_display: bool,
+        pub break_on_sound: bool,
+        commands: Option<Receiver<String>>,
+        /// Where the breakpoints and settings are saved as they change, if anywhere
+        session_path: Option<PathBuf>,
+        /// The registers when execution last stopped, and when it stopped before that
+        stopped_at: Option<Registers>,
+        changed_from: Option<Registers>,
+    }
+
+    /// What is remembered of a debugging session from one run of a ROM to the next
+    #[derive(Default, Serialize, Deserialize)]
+    struct Session {
+        #[serde(default)]
+        breakpoints: BTreeSet<usize>,
+        #[serde(default)]
+        break_on_display: bool,
+        #[serde(default)]
+        break_on_sound: bool,
+    }
+
+    /// The registers an instruction can change, to tell which ones it did
+    #[derive(Clone, Copy, PartialEq)]
+    struct Registers {
+        v: [u8; 16],
+        i: u16,
+    }
+
+    impl Registers {
+        fn of(cpu: &dyn Chip8Core) -> Registers {
+            Registers { v: *cpu.registers(), i: cpu.i_register() }
+        }
+    }
+
+    impl Debugger {
+        pub fn new() -> Debugger {
+            Debugger {
+                paused: false,
+                steps: 0,
+                breakpoints: BTreeSet::new(),
+                resumed_from: None,
+                break_on_display: false,
+                break_on_sound: false,
+                commands: None,
+                session_path: None,
+                stopped_at: None,
+                changed_from: None,
+            }
+        }
+
+        /// Creates a debugger with the breakpoints and settings of the last session with a ROM,
+        /// which are saved again whenever they change.
+        pub fn load(rom_hash: &str) -> Chip8Result<Debugger> {
+            let path = storage::data_dir("debug")?.join(format!("{}.toml", rom_hash));
+            let session: Session = if path.exists() {
+                toml::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| format!("Invalid debugger session in {}: {}", path.display(), e))?
+            } else {
+                Session::default()
+            };
+
+            if !session.breakpoints.is_empty() {
+                println!("Restored {} breakpoint(s) from the last session", session.breakpoints.len());
+            }
+            Ok(Debugger {
+                breakpoints: session.breakpoints,
+                break_on_display: session.break_on_display,
+                break_on_sound: session.break_on_sound,
+                session_path: Some(path),
+                ..Debugger::new()
+            })
+        }
+
+        fn save_session(&self) {
+            let path = match &self.session_path {
+                Some(path) => path,
+                None => return,
+            };
+            let session = Session {
+                breakpoints: self.breakpoints.clone(),
+                break_on_display: self.break_on_display,
+                break_on_sound: self.break_on_sound,
+            };
+            let saved = toml::to_string(&session)
+                .map_err(|e| e.to_string())
+                .and_then(|text| fs::write(path, text).map_err(|e| e.to_string()));
+            if let Err(e) = saved {
+                println!("Could not save the debugger session to {}: {}", path.display(), e);
+            }
+        }
+
+        pub fn is_paused(&self) -> bool {
+            self.paused && self.steps == 0
+        }
+
+        pub fn pause(&mut self, cpu: &dyn Chip8Core, reason: &str) {
+            if self.commands.is_none() {
+                self.commands = Some(spawn_reader());
+                println!("{}", HELP);
+            }
+            self.paused = true;
+            self.steps = 0;
+            println!("{}", reason);
+            self.stopped(cpu);
+        }
+
+        pub fn resume(&mut self, cpu: &dyn Chip8Core) {
+            self.paused = false;
+            self.resumed_from = Some(cpu.pc());
+        }
+
+        /// Runs the commands typed since the last call, without blocking.
+        pub fn handle_commands(&mut self, cpu: &mut dyn Chip8Core) {
+            let lines: Vec<String> = match &self.commands {
+                Some(commands) => commands.try_iter().collect(),
+                None => return,
+            };
+            for line in lines {
+                self.run_command(line.trim(), cpu);
+                if self.is_paused() {
+                    prompt();
+                }
+            }
+        }
+
+        fn run_command(&mut self, line: &str, cpu: &mut dyn Chip8Core) {
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => return,
+            };
+            let arg = words.next();
+
+            match command {
+                "c" | "continue" => self.resume(cpu),
+                "s" | "step" => match arg.map(str::parse).unwrap_or(Ok(1)) {
+                    Ok(steps) => {
+                        self.paused = true;
+                        self.steps = steps;
+                    }
+                    Err(_) => println!("Invalid step count: {}", arg.unwrap_or_default()),
+                },
+                "b" | "break" => match arg.and_then(parse_address) {
+                    Some(addr) => {
+                        self.breakpoints.insert(addr);
+                        self.save_session();
+                        println!("Breakpoint set at 0x{:03x}", addr);
+                    }
+                    None => println!("Usage: break <ADDR>"),
+                },
+                "d" | "delete" => match arg.and_then(parse_address) {
+                    Some(addr) if self.breakpoints.remove(&addr) => {
+                        self.save_session();
+                        println!("Breakpoint removed from 0x{:03x}", addr);
+                    }
+                    Some(addr) => println!("No breakpoint at 0x{:03x}", addr),
+                    None => println!("Usage: delete <ADDR>"),
+                },
+                "l" | "list" => {
+                    if self.breakpoints.is_empty() {
+                        println!("No breakpoints");
+                    }
+                    for addr in self.breakpoints.iter() {
+                        println!("0x{:03x}: {:04X}", addr, cpu.opcode_at(*addr));
+                    }
+                }
+                "r" | "regs" => self.print_registers(cpu),
+                "p" | "print" => match expr::evaluate(line[command.len()..].trim(), cpu) {
+                    Ok(value) => println!("{}", value),
+                    Err(e) => println!("{}", e),
+                },
+                "disp" | "display" => print!("{}", dump::format_display(cpu.display())),
+                "set" => match expr::assign(&line[command.len()..], cpu) {
+                    Ok(()) => {}
+                    Err(e) => println!("{}", e),
+                },
+                "bd" | "break-display" => {
+                    self.break_on_display = match arg {
+                        Some("on") => true,
+                        Some("off") => false,
+                        _ => !self.break_on_display,
+                    };
+                    self.save_session();
+                    println!("Break on display change is {}", if self.break_on_display { "on" } else { "off" });
+                }
+                "bs" | "break-sound" => {
+                    self.break_on_sound = match arg {
+                        Some("on") => true,
+                        Some("off") => false,
+                        _ => !self.break_on_sound,
+                    };
+                    self.save_session();
+                    println!("Break on sound is {}", if self.break_on_sound { "on" } else { "off" });
+                }
+                "h" | "help" => println!("{}", HELP),
+                _ => println!("Unknown command: {}, type help for a list of commands", command),
+            }
+        }
+
+        /// Called before each instruction, returns whether it may be executed.
+        pub fn before_instruction(&mut self, cpu: &dyn Chip8Core) -> bool {
+            if self.paused {
+                if self.steps == 0 {
+                    return false;
+                }
+                self.steps -= 1;
+                return true;
+            }
+
+            let resumed_from = self.resumed_from.take();
+            if self.breakpoints.contains(&cpu.pc()) && resumed_from != Some(cpu.pc()) {
+                self.pause(cpu, &format!("Breakpoint at 0x{:03x}", cpu.pc()));
+                return false;
+            }
+
+            true
+        }
+
+        /// Called after each instruction with the instruction that was executed.
+        pub fn after_instruction(&mut self, cpu: &dyn Chip8Core, instruction: Instruction) {
+            let draws = matches!(instruction, Instruction::Draw(..) | Instruction::ClearScreen);
+            let beeps = matches!(instruction, Instruction::SetSound(..)) && cpu.is_sound_playing();
+            if self.break_on_display && draws && !self.paused {
+                self.pause(cpu, &format!("Display changed by {}", instruction));
+            } else if self.break_on_sound && beeps && !self.paused {
+                self.pause(cpu, &format!("Sound started by {}", instruction));
+            } else if self.paused && self.steps == 0 {
+                self.stopped(cpu);
+            }
+        }
+
+        /// Shows where execution stopped and the registers changed since it last did.
+        fn stopped(&mut self, cpu: &dyn Chip8Core) {
+            self.changed_from = self.stopped_at.replace(Registers::of(cpu));
+            if let Some(old) = self.changed_from {
+                let new = Registers::of(cpu);
+                let mut changes: Vec<String> = (0..16)
+                    .filter(|&r| old.v[r] != new.v[r])
+                    .map(|r| format!("V{:X}: {:02x} -> {:02x}", r, old.v[r], new.v[r]))
+                    .collect();
+                if old.i != new.i {
+                    changes.push(format!("I: {:03x} -> {:03x}", old.i, new.i));
+                }
+                if !changes.is_empty() {
+                    println!("{}", changes.join(", "));
+                }
+            }
+            print_location(cpu);
+        }
+
+        /// Prints the registers, with the ones changed since the previous stop highlighted
+        /// and followed by their old value.
+        fn print_registers(&self, cpu: &dyn Chip8Core) {
+            let new = Registers::of(cpu);
+            let old = self.changed_from.unwrap_or(new);
+            let color = io::stdout().is_terminal();
+            let highlight = |text: String, changed: bool, was: String| match (changed, color) {
+                (false, _) => text,
+                (true, true) => format!("\x1b[1;33m{}\x1b[0m (was {})", text, was),
+                (true, false) => format!("*{} (was {})", text, was),
+            };
+
+            println!("PC=0x{:03x} {} SP={} DT={} ST={}", cpu.pc(),
+                highlight(format!("I=0x{:03x}", new.i), old.i != new.i, format!("0x{:03x}", old.i)),
+                cpu.stack_pointer(), cpu.delay_timer(), cpu.sound_timer());
+            for r in 0..16 {
+                print!("{}{}", highlight(format!("V{:X}={:02x}", r, new.v[r]), old.v[r] != new.v[r], format!("{:02x}", old.v[r])),
+                    if r % 8 == 7 { "\n" } else { " " });
+            }
+        }
+    }
+
+    impl Default for Debugger {
+        fn default() -> Debugger {
+            Debugger::new()
+        }
+    }
+
+    fn parse_address(arg: &str) -> Option<usize> {
+        let digits = arg.trim_start_matches("0x");
+        usize::from_str_radix(digits, 16).ok()
+    }
+
+    fn print_location(cpu: &dyn Chip8Core) {
+        println!("0x{:03x}: {:04X}  {}", cpu.pc(), cpu.opcode_at(cpu.pc()), cpu.next_instruction());
+        prompt();
+    }
+
+    fn prompt() {
+        print!("(rschip8) ");
+        io::stdout().flush().ok();
+    }
+
+    /// Reads lines from stdin on a separate thread, so the emulator never blocks on input.
+    fn spawn_reader() -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let sent = line.map(|line| sender.send(line).is_ok()).unwrap_or(false);
+                if !sent {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
@@ -0,0 +1,11 @@
+pub use dialog::pick_program;
+
+pub mod dialog {
+    const PATTERNS: [&str; 5] = ["*.ch8", "*.c8", "*.rom", "*.hex", "*.rc8"];
+
+    /// Asks for a program to run with the system's file picker, for when rschip8 is started
+    /// from a file manager or menu rather than a shell. Returns `None` if it was cancelled.
+    pub fn pick_program() -> Option<String> {
+        tinyfiledialogs::open_file_dialog("Open a CHIP-8 program", "", Some((&PATTERNS, "CHIP-8 programs")))
+    }
+}
@@ -0,0 +1,95 @@
+pub use dump::write_dump;
+pub use dump::format_dump;
+pub use dump::format_display;
+pub use dump::Trace;
+
+pub mod dump {
+    use std::{collections::VecDeque, fmt::Write, fs};
+
+    use crate::emulator::{Chip8Result, engine::Chip8Core, instruction, ui};
+
+    const BYTES_PER_LINE: usize = 16;
+    /// Instructions kept in a trace
+    const TRACE_LENGTH: usize = 32;
+
+    /// The last instructions executed, oldest first, to see what a program was doing
+    /// when it was dumped.
+    #[derive(Default)]
+    pub struct Trace {
+        entries: VecDeque<(usize, u16)>,
+    }
+
+    impl Trace {
+        pub fn new() -> Trace {
+            Trace::default()
+        }
+
+        /// Records the instruction `cpu` is about to execute.
+        pub fn record(&mut self, cpu: &dyn Chip8Core) {
+            if self.entries.len() == TRACE_LENGTH {
+                self.entries.pop_front();
+            }
+            self.entries.push_back((cpu.pc(), cpu.opcode_at(cpu.pc())));
+        }
+    }
+
+    /// Writes the machine's registers, stack, display and memory to `path` as text,
+    /// along with the error that stopped the program and the last instructions, if any.
+    pub fn write_dump(cpu: &dyn Chip8Core, error: Option<&str>, trace: Option<&Trace>, path: &str) -> Chip8Result<()> {
+        let mut out = String::new();
+        if let Some(error) = error {
+            writeln!(out, "Stopped by error: {}\n", error)?;
+        }
+        if let Some(trace) = trace {
+            writeln!(out, "Last instructions:")?;
+            for (pc, opcode) in trace.entries.iter() {
+                writeln!(out, "0x{:03x}: {:04X}  {}", pc, opcode, instruction::decode(*opcode))?;
+            }
+            out.push('\n');
+        }
+        out.push_str(&format_dump(cpu));
+        fs::write(path, out)?;
+        Ok(())
+    }
+
+    pub fn format_dump(cpu: &dyn Chip8Core) -> String {
+        let mut out = String::new();
+        // Writing to a String can't fail
+        let _ = write_dump_to(&mut out, cpu);
+        out
+    }
+
+    /// Draws the display as text, with `#` for lit pixels and `.` for unlit ones.
+    pub fn format_display(buffer: &ui::Buffer) -> String {
+        let mut out = String::new();
+        for y in 0..buffer.height() {
+            for x in 0..ui::WIDTH as usize {
+                out.push(if buffer.get(x, y) { '#' } else { '.' });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn write_dump_to(out: &mut String, cpu: &dyn Chip8Core) -> std::fmt::Result {
+        writeln!(out, "Registers:\n{}", cpu)?;
+
+        write!(out, "Stack:")?;
+        for addr in cpu.stack() {
+            write!(out, " 0x{:03x}", addr)?;
+        }
+        writeln!(out, "\n")?;
+
+        writeln!(out, "Display:\n{}", format_display(cpu.display()))?;
+
+        writeln!(out, "Memory:")?;
+        for (line, bytes) in cpu.memory().chunks(BYTES_PER_LINE).enumerate() {
+            write!(out, "{:03x}:", line * BYTES_PER_LINE)?;
+            for byte in bytes {
+                write!(out, " {:02x}", byte)?;
+            }
+            out.push('\n');
+        }
+        Ok(())
+    }
+}
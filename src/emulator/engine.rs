@@ -0,0 +1,107 @@
+pub use engine::Chip8Core;
+
+pub mod engine {
+    use std::fmt;
+
+    use crate::emulator::{Chip8Result, cpu::{CpuError, Event}, hooks::Hooks, input::Key, instruction::{self, Instruction}, quirks::Quirks, ui, variants::Platform};
+
+    /// What a frontend needs from an interpreter: loading a program, running it, feeding it
+    /// keys, showing its display, and the machine state the debugger, save states and
+    /// replays read and write. The window, the headless runner and the tools around them
+    /// only go through it. `CPU` is the only interpreter so far, and runs CHIP-8
+    /// instructions for every platform; SUPER-CHIP and XO-CHIP interpreters would plug in
+    /// here.
+    pub trait Chip8Core {
+        /// The platform the interpreter was configured for
+        fn platform(&self) -> Platform;
+
+        /// The callbacks run as the program does
+        fn hooks_mut(&mut self) -> &mut Hooks;
+
+        /// Replaces the program and starts it from the beginning.
+        fn load(&mut self, program: &[u8]) -> Chip8Result<()>;
+
+        /// Starts the program over, keeping the configuration.
+        fn reset(&mut self);
+
+        /// Copies the machine state from `other`, keeping the configuration, e.g. to restore
+        /// a save state.
+        fn load_machine_state(&mut self, other: &Self) where Self: Sized;
+
+        /// Checks the invariants a deserialized machine might break, since it may come from
+        /// an edited or corrupted file.
+        fn validate(&self) -> Chip8Result<()>;
+
+        fn quirks(&self) -> Quirks;
+
+        fn set_quirks(&mut self, quirks: Quirks);
+
+        /// The address of the next instruction
+        fn pc(&self) -> usize;
+
+        fn set_pc(&mut self, pc: usize) -> Chip8Result<()>;
+
+        /// The V registers, V0 to VF
+        fn registers(&self) -> &[u8; 16];
+
+        /// Sets VX. Panics if `x` isn't a register number, i.e. 0 to F.
+        fn set_register(&mut self, x: u8, value: u8);
+
+        fn i_register(&self) -> u16;
+
+        fn set_i_register(&mut self, value: u16);
+
+        fn delay_timer(&self) -> u8;
+
+        fn set_delay_timer(&mut self, value: u8);
+
+        fn sound_timer(&self) -> u8;
+
+        fn set_sound_timer(&mut self, value: u8);
+
+        fn stack_pointer(&self) -> usize;
+
+        /// The return addresses currently on the stack, innermost last.
+        fn stack(&self) -> &[u16];
+
+        fn memory(&self) -> &[u8];
+
+        /// Writes `bytes` to memory starting from `addr`, as the program itself would.
+        fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Chip8Result<()>;
+
+        /// Returns the two bytes at `addr` as an opcode.
+        fn opcode_at(&self, addr: usize) -> u16;
+
+        /// Decodes the instruction that will be executed next
+        fn next_instruction(&self) -> Instruction {
+            instruction::decode(self.opcode_at(self.pc()))
+        }
+
+        /// Executes one instruction.
+        fn step(&mut self) -> Result<Option<Event>, CpuError>;
+
+        /// Counts the timers down, to be called 60 times per second.
+        fn tick_timers(&mut self) -> Option<Event>;
+
+        fn display(&self) -> &ui::Buffer;
+
+        fn set_key(&mut self, key: Key, pressed: bool);
+
+        /// The keys being held, as a mask with a bit for each key by its CHIP-8 code.
+        fn held_keys(&self) -> u16;
+
+        fn is_sound_playing(&self) -> bool;
+    }
+
+    /// Shows the registers, as in dumps.
+    impl fmt::Display for dyn Chip8Core + '_ {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "PC=0x{:03x} I=0x{:03x} SP={} DT={} ST={}",
+                self.pc(), self.i_register(), self.stack_pointer(), self.delay_timer(), self.sound_timer())?;
+            for (r, value) in self.registers().iter().enumerate() {
+                write!(f, "V{:X}={:02x}{}", r, value, if r % 8 == 7 { "\n" } else { " " })?;
+            }
+            Ok(())
+        }
+    }
+}
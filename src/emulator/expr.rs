@@ -0,0 +1,290 @@
+pub use expr::Value;
+pub use expr::evaluate;
+pub use expr::assign;
+
+pub mod expr {
+    use std::{convert::TryFrom, fmt};
+
+    use crate::emulator::{Chip8Result, engine::Chip8Core};
+
+    /// The result of an expression: a number, or a range of memory.
+    pub enum Value {
+        Number(i64),
+        Bytes(usize, Vec<u8>),
+    }
+
+    impl fmt::Display for Value {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Value::Number(n) => write!(f, "0x{:x}  {}  0b{:b}", n, n, n),
+                Value::Bytes(start, bytes) => {
+                    for (offset, byte) in bytes.iter().enumerate() {
+                        if offset > 0 {
+                            writeln!(f)?;
+                        }
+                        write!(f, "0x{:03x}: 0x{:02x}  {:3}  0b{:08b}", start + offset, byte, byte, byte)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Evaluates an expression over the state of the CPU, such as `V[3] * 2 + I` or `mem[I..I+5]`.
+    ///
+    /// Numbers can be decimal, or hex and binary with `0x` and `0b`. The registers are `V0`-`VF`
+    /// (or `V[n]`), `I`, `PC`, `SP`, `DT` and `ST`, and memory is read with `mem[addr]`. The
+    /// usual arithmetic and bitwise operators are supported, with C's precedence. A range of
+    /// memory, `mem[start..end]`, can only be the whole expression.
+    pub fn evaluate(text: &str, cpu: &dyn Chip8Core) -> Chip8Result<Value> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0, cpu };
+        if let [Token::Ident(name), Token::Open('['), ..] = &parser.tokens[..] {
+            if name == "MEM" && parser.tokens.contains(&Token::Range) {
+                parser.position = 2;
+                let start = parser.address()?;
+                parser.expect(Token::Range)?;
+                let end = parser.address()?;
+                parser.expect(Token::Close(']'))?;
+                parser.end()?;
+                if start > end || end > cpu.memory().len() {
+                    return Err(format!("Invalid memory range: 0x{:x}..0x{:x}", start, end).into());
+                }
+                return Ok(Value::Bytes(start, cpu.memory()[start..end].to_vec()));
+            }
+        }
+
+        let value = parser.expression(0)?;
+        parser.end()?;
+        Ok(Value::Number(value))
+    }
+
+    /// Sets a register or byte of memory to the value of an expression, given as
+    /// `<TARGET> = <EXPR>`, e.g. `V3 = V3 + 1` or `mem[I + 2] = 0xff`. The targets are
+    /// the registers and memory that `evaluate` can read, except for SP.
+    pub fn assign(text: &str, cpu: &mut dyn Chip8Core) -> Chip8Result<()> {
+        let (target, expression) = text.split_once('=').ok_or("Expected <TARGET> = <EXPR>")?;
+        let value = match evaluate(expression, cpu)? {
+            Value::Number(value) => value,
+            Value::Bytes(..) => return Err("Only a single value can be assigned".into()),
+        };
+
+        let (name, index) = {
+            let mut parser = Parser { tokens: tokenize(target)?, position: 0, cpu };
+            let name = match parser.next() {
+                Some(Token::Ident(name)) => name,
+                _ => return Err(format!("Invalid target: {}", target.trim()).into()),
+            };
+            let index = if name == "V" || name == "MEM" {
+                parser.expect(Token::Open('['))?;
+                let index = parser.address()?;
+                parser.expect(Token::Close(']'))?;
+                Some(index)
+            } else {
+                None
+            };
+            parser.end()?;
+            (name, index)
+        };
+
+        let byte = || u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value));
+        match (name.as_str(), index) {
+            ("V", Some(x)) if x < 16 => cpu.set_register(x as u8, byte()?),
+            ("MEM", Some(addr)) => cpu.write_memory(addr, &[byte()?])?,
+            ("I", _) => cpu.set_i_register(u16::try_from(value).map_err(|_| format!("{} doesn't fit in I", value))?),
+            ("PC", _) => cpu.set_pc(usize::try_from(value).map_err(|_| format!("Invalid address: {}", value))?)?,
+            ("DT", _) => cpu.set_delay_timer(byte()?),
+            ("ST", _) => cpu.set_sound_timer(byte()?),
+            _ => match name.strip_prefix('V').map(|digit| u8::from_str_radix(digit, 16)) {
+                Some(Ok(x)) if name.len() == 2 => cpu.set_register(x, byte()?),
+                _ => return Err(format!("Cannot assign to {}", target.trim()).into()),
+            },
+        }
+        Ok(())
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    enum Token {
+        Number(i64),
+        Ident(String),
+        Operator(&'static str),
+        Open(char),
+        Close(char),
+        Range,
+    }
+
+    /// Binary operators from the loosest binding to the tightest
+    const PRECEDENCE: [&[&str]; 6] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+    fn tokenize(text: &str) -> Chip8Result<Vec<Token>> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let rest: String = chars[i..].iter().take(2).collect();
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_alphanumeric() {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect::<String>().to_uppercase();
+                tokens.push(if c.is_ascii_digit() {
+                    Token::Number(parse_number(&word)?)
+                } else {
+                    Token::Ident(word)
+                });
+            } else if rest == ".." {
+                tokens.push(Token::Range);
+                i += 2;
+            } else if let Some(op) = ["<<", ">>"].iter().find(|op| **op == rest) {
+                tokens.push(Token::Operator(op));
+                i += 2;
+            } else if let Some(op) = ["|", "^", "&", "+", "-", "*", "/", "%", "~"].iter().find(|op| op.starts_with(c)) {
+                tokens.push(Token::Operator(op));
+                i += 1;
+            } else if c == '(' || c == '[' {
+                tokens.push(Token::Open(c));
+                i += 1;
+            } else if c == ')' || c == ']' {
+                tokens.push(Token::Close(c));
+                i += 1;
+            } else {
+                return Err(format!("Unexpected character: {}", c).into());
+            }
+        }
+        Ok(tokens)
+    }
+
+    fn parse_number(word: &str) -> Chip8Result<i64> {
+        let parsed = if let Some(hex) = word.strip_prefix("0X") {
+            i64::from_str_radix(hex, 16)
+        } else if let Some(binary) = word.strip_prefix("0B") {
+            i64::from_str_radix(binary, 2)
+        } else {
+            word.parse()
+        };
+        parsed.map_err(|_| format!("Invalid number: {}", word).into())
+    }
+
+    struct Parser<'a> {
+        tokens: Vec<Token>,
+        position: usize,
+        cpu: &'a dyn Chip8Core,
+    }
+
+    impl<'a> Parser<'a> {
+        fn next(&mut self) -> Option<Token> {
+            let token = self.tokens.get(self.position).cloned();
+            self.position += 1;
+            token
+        }
+
+        fn peek(&self) -> Option<&Token> {
+            self.tokens.get(self.position)
+        }
+
+        fn expect(&mut self, expected: Token) -> Chip8Result<()> {
+            match self.next() {
+                Some(token) if token == expected => Ok(()),
+                _ => Err(format!("Expected {}", describe(&expected)).into()),
+            }
+        }
+
+        fn end(&self) -> Chip8Result<()> {
+            match self.peek() {
+                None => Ok(()),
+                Some(token) => Err(format!("Unexpected {}", describe(token)).into()),
+            }
+        }
+
+        /// Parses operators binding at least as tightly as `level` in `PRECEDENCE`.
+        fn expression(&mut self, level: usize) -> Chip8Result<i64> {
+            if level == PRECEDENCE.len() {
+                return self.unary();
+            }
+            let mut value = self.expression(level + 1)?;
+            while let Some(Token::Operator(op)) = self.peek() {
+                let op = *op;
+                if !PRECEDENCE[level].contains(&op) {
+                    break;
+                }
+                self.position += 1;
+                let rhs = self.expression(level + 1)?;
+                value = match op {
+                    "|" => value | rhs,
+                    "^" => value ^ rhs,
+                    "&" => value & rhs,
+                    "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                    ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                    "+" => value.wrapping_add(rhs),
+                    "-" => value.wrapping_sub(rhs),
+                    "*" => value.wrapping_mul(rhs),
+                    _ if rhs == 0 => return Err("Division by zero".into()),
+                    "/" => value.wrapping_div(rhs),
+                    _ => value.wrapping_rem(rhs),
+                };
+            }
+            Ok(value)
+        }
+
+        fn unary(&mut self) -> Chip8Result<i64> {
+            match self.next() {
+                Some(Token::Operator("-")) => Ok(self.unary()?.wrapping_neg()),
+                Some(Token::Operator("~")) => Ok(!self.unary()?),
+                Some(Token::Number(n)) => Ok(n),
+                Some(Token::Open('(')) => {
+                    let value = self.expression(0)?;
+                    self.expect(Token::Close(')'))?;
+                    Ok(value)
+                }
+                Some(Token::Ident(name)) => self.variable(&name),
+                Some(token) => Err(format!("Unexpected {}", describe(&token)).into()),
+                None => Err("Unexpected end of expression".into()),
+            }
+        }
+
+        fn variable(&mut self, name: &str) -> Chip8Result<i64> {
+            let cpu = self.cpu;
+            match name {
+                "I" => Ok(cpu.i_register() as i64),
+                "PC" => Ok(cpu.pc() as i64),
+                "SP" => Ok(cpu.stack_pointer() as i64),
+                "DT" => Ok(cpu.delay_timer() as i64),
+                "ST" => Ok(cpu.sound_timer() as i64),
+                "V" | "MEM" => {
+                    self.expect(Token::Open('['))?;
+                    let index = self.address()?;
+                    self.expect(Token::Close(']'))?;
+                    let values = if name == "V" { &cpu.registers()[..] } else { cpu.memory() };
+                    values.get(index)
+                        .map(|value| *value as i64)
+                        .ok_or_else(|| format!("{}[0x{:x}] is out of bounds", name, index).into())
+                }
+                _ => match name.strip_prefix('V').map(|digit| u8::from_str_radix(digit, 16)) {
+                    Some(Ok(r)) if name.len() == 2 => Ok(cpu.registers()[r as usize] as i64),
+                    _ => Err(format!("Unknown name: {}", name).into()),
+                },
+            }
+        }
+
+        fn address(&mut self) -> Chip8Result<usize> {
+            let value = self.expression(0)?;
+            if value < 0 {
+                return Err(format!("Negative address: {}", value).into());
+            }
+            Ok(value as usize)
+        }
+    }
+
+    fn describe(token: &Token) -> String {
+        match token {
+            Token::Number(n) => format!("number {}", n),
+            Token::Ident(name) => format!("name {}", name),
+            Token::Operator(op) => format!("operator {}", op),
+            Token::Open(c) | Token::Close(c) => format!("'{}'", c),
+            Token::Range => "'..'".to_string(),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+pub use gamepad::Rumble;
+
+pub mod gamepad {
+    use sdl2::{GameControllerSubsystem, Sdl};
+    use sdl2::controller::GameController;
+
+    use crate::emulator::Chip8Result;
+
+    const STRENGTH: u16 = 0x6000;
+    /// How long each rumble lasts. It's renewed on every frame while the sound timer is active,
+    /// so it stops shortly after the timer does even if the update that stops it is missed.
+    const DURATION_MS: u32 = 100;
+
+    /// Rumbles the connected controllers while the sound timer is active.
+    pub struct Rumble {
+        subsystem: GameControllerSubsystem,
+        controllers: Vec<GameController>,
+        playing: bool,
+    }
+
+    impl Rumble {
+        pub fn new(sdl_context: &Sdl) -> Chip8Result<Rumble> {
+            Ok(Rumble {
+                subsystem: sdl_context.game_controller()?,
+                controllers: Vec::new(),
+                playing: false,
+            })
+        }
+
+        /// Opens a newly connected controller, given its joystick index.
+        pub fn connect(&mut self, index: u32) {
+            match self.subsystem.open(index) {
+                Ok(controller) => self.controllers.push(controller),
+                Err(e) => eprintln!("Could not open controller {}: {}", index, e),
+            }
+        }
+
+        /// Forgets a disconnected controller, given its instance id.
+        pub fn disconnect(&mut self, id: i32) {
+            self.controllers.retain(|controller| controller.instance_id() != id);
+        }
+
+        pub fn update(&mut self, playing: bool) {
+            if !playing && !self.playing {
+                return;
+            }
+            let (strength, duration) = if playing { (STRENGTH, DURATION_MS) } else { (0, 0) };
+            for controller in self.controllers.iter_mut() {
+                // Controllers without haptics simply don't rumble
+                controller.set_rumble(strength, strength, duration).ok();
+            }
+            self.playing = playing;
+        }
+    }
+}
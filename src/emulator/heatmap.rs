@@ -0,0 +1,71 @@
+pub use heatmap::HeatMap;
+pub use heatmap::HEAT_MAP_KEY;
+
+pub mod heatmap {
+    use sdl2::keyboard::Keycode;
+
+    use crate::emulator::{engine::Chip8Core, instruction::Instruction};
+
+    pub const HEAT_MAP_KEY: Keycode = Keycode::F9;
+
+    /// How much of its heat a byte keeps from one frame to the next, out of 256
+    const COOLING: u32 = 232;
+
+    /// Tracks how recently each byte of memory was read, written and executed. Activity
+    /// is worked out from the instruction about to run, so the CPU doesn't have to keep
+    /// track of it when the heat map isn't shown.
+    pub struct HeatMap {
+        /// Read, write and execute heat of every byte, from 0 to 255
+        heat: Vec<[u8; 3]>,
+    }
+
+    impl HeatMap {
+        const READ: usize = 0;
+        const WRITE: usize = 1;
+        const EXECUTE: usize = 2;
+
+        pub fn new(memory_size: usize) -> HeatMap {
+            HeatMap { heat: vec![[0; 3]; memory_size] }
+        }
+
+        /// Records the memory accesses of `instruction`, which must be the one `cpu` is about to execute.
+        pub fn record(&mut self, cpu: &dyn Chip8Core, instruction: Instruction) {
+            let i = cpu.i_register() as usize;
+            self.touch(cpu.pc(), 2, HeatMap::EXECUTE);
+            match instruction {
+                Instruction::Draw(_, _, n) => self.touch(i, n as usize, HeatMap::READ),
+                Instruction::StoreBcd(_) => self.touch(i, 3, HeatMap::WRITE),
+                Instruction::StoreRegisters(x) => self.touch(i, x as usize + 1, HeatMap::WRITE),
+                Instruction::ReadRegisters(x) => self.touch(i, x as usize + 1, HeatMap::READ),
+                _ => {}
+            }
+        }
+
+        /// Lets every byte cool down a little, to be called once per frame.
+        pub fn cool(&mut self) {
+            for byte in self.heat.iter_mut() {
+                for heat in byte.iter_mut() {
+                    *heat = (*heat as u32 * COOLING / 256) as u8;
+                }
+            }
+        }
+
+        /// The color of a byte, with red for writes, green for reads and blue for execution.
+        /// Bytes that aren't zero but haven't been touched lately are shown in dark gray.
+        pub fn color(&self, addr: usize, value: u8) -> (u8, u8, u8) {
+            let [read, write, execute] = self.heat[addr];
+            let base = if value != 0 { 40 } else { 0 };
+            (write.max(base), read.max(base), execute.max(base))
+        }
+
+        /// Marks `len` bytes from `addr`. An access that doesn't fit in memory touches nothing,
+        /// since the CPU faults on it rather than wrapping around or doing part of it.
+        fn touch(&mut self, addr: usize, len: usize, access: usize) {
+            if let Some(bytes) = self.heat.get_mut(addr..addr + len) {
+                for byte in bytes.iter_mut() {
+                    byte[access] = 255;
+                }
+            }
+        }
+    }
+}
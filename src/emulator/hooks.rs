@@ -0,0 +1,46 @@
+pub use hooks::Hooks;
+
+pub mod hooks {
+    use crate::emulator::{engine::Chip8Core, instruction::Instruction, ui};
+
+    pub type InstructionHook = Box<dyn FnMut(&dyn Chip8Core, Instruction) + Send>;
+    pub type DrawHook = Box<dyn FnMut(&dyn Chip8Core, &ui::Buffer) + Send>;
+    pub type KeyWaitHook = Box<dyn FnMut(&dyn Chip8Core, u8) + Send>;
+    pub type TimerHook = Box<dyn FnMut(&dyn Chip8Core) + Send>;
+
+    /// Callbacks an interpreter runs at points of interest, so tracing, cheats and visualizations
+    /// don't need changes to the interpreter itself. A hook that isn't set costs a single check.
+    #[derive(Default)]
+    pub struct Hooks {
+        pub(crate) before_instruction: Option<InstructionHook>,
+        pub(crate) after_draw: Option<DrawHook>,
+        pub(crate) on_key_wait: Option<KeyWaitHook>,
+        pub(crate) on_timer_tick: Option<TimerHook>,
+    }
+
+    impl Hooks {
+        /// Runs before each instruction is executed, with the instruction at the PC.
+        pub fn before_instruction(&mut self, hook: impl FnMut(&dyn Chip8Core, Instruction) + Send + 'static) {
+            self.before_instruction = Some(Box::new(hook));
+        }
+
+        /// Runs after each instruction that changes the display, with the display's contents.
+        pub fn after_draw(&mut self, hook: impl FnMut(&dyn Chip8Core, &ui::Buffer) + Send + 'static) {
+            self.after_draw = Some(Box::new(hook));
+        }
+
+        /// Runs whenever FX0A waits for a key, with the register the key will be stored in.
+        pub fn on_key_wait(&mut self, hook: impl FnMut(&dyn Chip8Core, u8) + Send + 'static) {
+            self.on_key_wait = Some(Box::new(hook));
+        }
+
+        /// Runs whenever the delay and sound timers count down, i.e. at 60Hz.
+        pub fn on_timer_tick(&mut self, hook: impl FnMut(&dyn Chip8Core) + Send + 'static) {
+            self.on_timer_tick = Some(Box::new(hook));
+        }
+
+        pub fn clear(&mut self) {
+            *self = Hooks::default();
+        }
+    }
+}
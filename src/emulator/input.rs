@@ -1,18 +1,206 @@
 pub use input::Keyboard;
 pub use input::Key;
+pub use input::DEFAULT_KEYMAP;
+pub use input::Macro;
+pub use input::MacroStep;
+pub use input::MacroPlayer;
+pub use input::AutoFire;
+pub use input::Input;
+pub use input::InputSource;
 
 pub mod input {
     use sdl2::keyboard::Keycode;
     use std::collections::HashMap;
-    use strum::IntoEnumIterator;
     use strum_macros::EnumIter;
     use derivative::Derivative;
 
+    use crate::emulator::engine::Chip8Core;
+
+    /// Maps host keys to CHIP-8 keys and macros. Which keys are held is tracked by `Input`.
     pub struct Keyboard {
-        state: HashMap<Key, bool>,
+        keymap: HashMap<Keycode, Key>,
+        macros: HashMap<Keycode, Macro>,
+        /// Presses per second of the host keys that auto-repeat while held
+        autofire: HashMap<Keycode, u32>,
+    }
+
+    /// Where key presses come from
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum InputSource {
+        Keyboard,
+        Macro,
+        AutoFire,
+        /// Keys pressed by plugins, e.g. an agent playing the game or input from the network
+        Plugin,
+        Replay,
+    }
+
+    const SOURCES: usize = 5;
+
+    /// Merges the keys held by every source into the ones the CPU sees. A key is held while
+    /// any source holds it, unless a source took over, as a replay being played back does:
+    /// then only the keys of that source count.
+    #[derive(Default)]
+    pub struct Input {
+        /// The keys each source holds, as a mask with a bit for each key by its CHIP-8 code
+        held: [u16; SOURCES],
+        exclusive: Option<InputSource>,
+    }
+
+    impl Input {
+        pub fn new() -> Input {
+            Input::default()
+        }
+
+        pub fn set(&mut self, source: InputSource, key: Key, pressed: bool) {
+            let bit = 1 << key.chip8_code();
+            let held = &mut self.held[source as usize];
+            *held = if pressed { *held | bit } else { *held & !bit };
+        }
+
+        /// Replaces all the keys `source` holds with those in the `keys` mask.
+        pub fn set_keys(&mut self, source: InputSource, keys: u16) {
+            self.held[source as usize] = keys;
+        }
+
+        pub fn release_all(&mut self, source: InputSource) {
+            self.held[source as usize] = 0;
+        }
+
+        /// Ignores every other source until `hand_back` is called.
+        pub fn take_over(&mut self, source: InputSource) {
+            self.exclusive = Some(source);
+        }
+
+        pub fn hand_back(&mut self) {
+            if let Some(source) = self.exclusive.take() {
+                self.release_all(source);
+            }
+        }
+
+        /// The keys held once every source is accounted for, as a mask.
+        pub fn keys(&self) -> u16 {
+            match self.exclusive {
+                Some(source) => self.held[source as usize],
+                None => self.held.iter().fold(0, |keys, held| keys | held),
+            }
+        }
+
+        /// Presses and releases the keys on `cpu` so they match `keys`.
+        pub fn apply(&self, cpu: &mut dyn Chip8Core) {
+            let keys = self.keys();
+            let changed = keys ^ cpu.held_keys();
+            for code in (0..16).filter(|code| changed & (1 << code) != 0) {
+                if let Some(key) = Key::from_chip8_code(code) {
+                    cpu.set_key(key, keys & (1 << code) != 0);
+                }
+            }
+        }
+    }
+
+    /// A sequence of CHIP-8 key presses played back when a host key is pressed.
+    #[derive(Clone)]
+    pub struct Macro {
+        pub steps: Vec<MacroStep>,
+    }
+
+    /// Holds `key` down for `frames` frames, or waits that long if there's no key.
+    #[derive(Clone, Copy)]
+    pub struct MacroStep {
+        pub key: Option<Key>,
+        pub frames: u32,
+    }
+
+    /// Plays macros back, one frame at a time.
+    #[derive(Default)]
+    pub struct MacroPlayer {
+        /// The macros being played, with the step each is at and the frames left in it
+        playing: Vec<(Macro, usize, u32)>,
+    }
+
+    impl MacroPlayer {
+        pub fn new() -> MacroPlayer {
+            MacroPlayer::default()
+        }
+
+        pub fn start(&mut self, m: &Macro) {
+            if !m.steps.is_empty() {
+                self.playing.push((m.clone(), 0, 0));
+            }
+        }
+
+        /// Advances the macros by a frame, holding the keys of the steps they're at.
+        pub fn tick(&mut self, input: &mut Input) {
+            let mut keys = 0;
+            for (m, step, frames_left) in self.playing.iter_mut() {
+                if *frames_left == 0 {
+                    if *step == m.steps.len() {
+                        continue;
+                    }
+                    *frames_left = m.steps[*step].frames;
+                    *step += 1;
+                }
+                if let Some(key) = m.steps[*step - 1].key { keys |= 1 << key.chip8_code(); }
+                *frames_left = frames_left.saturating_sub(1);
+            }
+            self.playing.retain(|(m, step, frames_left)| *step < m.steps.len() || *frames_left > 0);
+            input.set_keys(InputSource::Macro, keys);
+        }
     }
 
-    #[derive(EnumIter, Eq, Derivative)]
+    /// Repeatedly presses and releases the CHIP-8 keys whose host keys are held with autofire on.
+    #[derive(Default)]
+    pub struct AutoFire {
+        /// The keys held, with the frames each press lasts and the frames since the key was held
+        held: Vec<(Key, u32, u32)>,
+    }
+
+    impl AutoFire {
+        pub fn new() -> AutoFire {
+            AutoFire::default()
+        }
+
+        pub fn hold(&mut self, key: Key, rate: u32) {
+            if !self.held.iter().any(|(k, _, _)| *k == key) {
+                self.held.push((key, (60 / rate.max(1)).max(2), 0));
+            }
+        }
+
+        pub fn release(&mut self, key: Key) {
+            self.held.retain(|(k, _, _)| *k != key);
+        }
+
+        /// Advances by a frame, keeping each held key down for the first half of every press.
+        pub fn tick(&mut self, input: &mut Input) {
+            let mut keys = 0;
+            for (key, period, frames) in self.held.iter_mut() {
+                *frames += 1;
+                if *frames % *period < *period / 2 { keys |= 1 << key.chip8_code(); }
+            }
+            input.set_keys(InputSource::AutoFire, keys);
+        }
+    }
+
+    pub const DEFAULT_KEYMAP: [(Keycode, Key); 16] = [
+        (Keycode::Num1, Key::Num1),
+        (Keycode::Num2, Key::Num2),
+        (Keycode::Num3, Key::Num3),
+        (Keycode::Num4, Key::C),
+        (Keycode::Q, Key::Num4),
+        (Keycode::W, Key::Num5),
+        (Keycode::E, Key::Num6),
+        (Keycode::R, Key::D),
+        (Keycode::A, Key::Num7),
+        (Keycode::S, Key::Num8),
+        (Keycode::D, Key::Num9),
+        (Keycode::F, Key::E),
+        (Keycode::Z, Key::A),
+        (Keycode::X, Key::Num0),
+        (Keycode::C, Key::B),
+        (Keycode::V, Key::F),
+    ];
+
+    #[derive(EnumIter, Eq, Clone, Copy, Derivative)]
     #[derivative(PartialEq, Hash)]
     pub enum Key {
         Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
@@ -42,28 +230,6 @@ pub mod input {
             }
         }
 
-        pub fn from_keycode(code: Keycode) -> Option<Key> {
-            match code {
-                Keycode::Num1 => Some(Key::Num1),
-                Keycode::Num2 => Some(Key::Num2),
-                Keycode::Num3 => Some(Key::Num3),
-                Keycode::Num4 => Some(Key::C),
-                Keycode::Q => Some(Key::Num4),
-                Keycode::W => Some(Key::Num5),
-                Keycode::E => Some(Key::Num6),
-                Keycode::R => Some(Key::D),
-                Keycode::A => Some(Key::Num7),
-                Keycode::S => Some(Key::Num8),
-                Keycode::D => Some(Key::Num9),
-                Keycode::F => Some(Key::E),
-                Keycode::Z => Some(Key::A),
-                Keycode::X => Some(Key::Num0),
-                Keycode::C => Some(Key::B),
-                Keycode::V => Some(Key::F),
-                _ => None
-            }
-        }
-
         pub fn chip8_code(&self) -> u8 {
             match *self {
                 Key::Num0 => 0,
@@ -87,35 +253,30 @@ pub mod input {
     }
 
     impl Keyboard {
-        pub fn new() -> Keyboard {
-            let mut kb = Keyboard { state: HashMap::new() };
-            for key in Key::iter() {
-                kb.state.insert(key, false);
-            }
-            kb
+        pub fn new(keymap: HashMap<Keycode, Key>, macros: HashMap<Keycode, Macro>, autofire: HashMap<Keycode, u32>) -> Keyboard {
+            Keyboard { keymap, macros, autofire }
         }
 
-        pub fn key_down(&mut self, code: Keycode) {
-            if let Some(key) = Key::from_keycode(code) {
-                self.state.insert(key, true);
-            }
+        pub fn keymap(&self) -> &HashMap<Keycode, Key> {
+            &self.keymap
         }
 
-        pub fn key_up(&mut self, code: Keycode) {
-            if let Some(key) = Key::from_keycode(code) {
-                self.state.insert(key, false);
-            }
+        pub fn set_keymap(&mut self, keymap: HashMap<Keycode, Key>) {
+            self.keymap = keymap;
         }
 
-        pub fn is_key_pressed(&self, code: u8) -> bool {
-            match Key::from_chip8_code(code) {
-                Some(key) => *self.state.get(&key).unwrap(),
-                None => false,
-            }
+        /// Returns the CHIP-8 key a host key is mapped to, if any.
+        pub fn key(&self, code: Keycode) -> Option<Key> {
+            self.keymap.get(&code).cloned()
+        }
+
+        pub fn macro_for(&self, code: Keycode) -> Option<&Macro> {
+            self.macros.get(&code)
         }
 
-        pub fn any_pressed_key(&self) -> Option<u8> {
-            Some(Key::iter().find(|key| *self.state.get(key).unwrap())?.chip8_code())
+        /// Returns how many times per second a host key repeats while held, if it has autofire on.
+        pub fn autofire_rate(&self, code: Keycode) -> Option<u32> {
+            self.autofire.get(&code).cloned()
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,147 @@
+pub use instruction::Instruction;
+pub use instruction::decode;
+
+pub mod instruction {
+    use std::fmt;
+
+    use crate::emulator::variants::ForeignOpcode;
+
+    /// A decoded CHIP-8 instruction. X and Y are register numbers, the other operands
+    /// are the immediate values of the opcode.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Instruction {
+        ClearScreen,
+        Return,
+        Sys(u16),
+        Jump(u16),
+        Call(u16),
+        SkipEqImm(u8, u8),
+        SkipNeqImm(u8, u8),
+        SkipEqReg(u8, u8),
+        LoadImm(u8, u8),
+        AddImm(u8, u8),
+        LoadReg(u8, u8),
+        Or(u8, u8),
+        And(u8, u8),
+        Xor(u8, u8),
+        AddReg(u8, u8),
+        Sub(u8, u8),
+        Shr(u8, u8),
+        SubN(u8, u8),
+        Shl(u8, u8),
+        SkipNeqReg(u8, u8),
+        SetI(u16),
+        JumpOffset(u16),
+        Random(u8, u8),
+        Draw(u8, u8, u8),
+        SkipKeyDown(u8),
+        SkipKeyUp(u8),
+        LoadDelay(u8),
+        WaitKey(u8),
+        SetDelay(u8),
+        SetSound(u8),
+        AddI(u8),
+        LoadFont(u8),
+        StoreBcd(u8),
+        StoreRegisters(u8),
+        ReadRegisters(u8),
+        Invalid(u16),
+    }
+
+    /// Decodes a CHIP-8 opcode. Opcodes that aren't CHIP-8 instructions decode to `Invalid`.
+    pub fn decode(opcode: u16) -> Instruction {
+        let high = (opcode >> 8) as u8;
+        let low = opcode as u8;
+        let a = (high & 0xf0) >> 4;
+        let x = high & 0xf;
+        let y = (low & 0xf0) >> 4;
+        let b = low & 0xf;
+        let nnn = opcode & 0xfff;
+
+        match (a, x, y, b) {
+            (0, 0, 0xe, 0) => Instruction::ClearScreen,
+            (0, 0, 0xe, 0xe) => Instruction::Return,
+            // Other 0NNN instructions call machine code routines, which are ignored,
+            // unless they are opcodes of a CHIP-8 extension that would go wrong silently
+            (0, _, _, _) if ForeignOpcode::identify(opcode).is_none() => Instruction::Sys(nnn),
+            (1, _, _, _) => Instruction::Jump(nnn),
+            (2, _, _, _) => Instruction::Call(nnn),
+            (3, _, _, _) => Instruction::SkipEqImm(x, low),
+            (4, _, _, _) => Instruction::SkipNeqImm(x, low),
+            (5, _, _, 0) => Instruction::SkipEqReg(x, y),
+            (6, _, _, _) => Instruction::LoadImm(x, low),
+            (7, _, _, _) => Instruction::AddImm(x, low),
+            (8, _, _, 0) => Instruction::LoadReg(x, y),
+            (8, _, _, 1) => Instruction::Or(x, y),
+            (8, _, _, 2) => Instruction::And(x, y),
+            (8, _, _, 3) => Instruction::Xor(x, y),
+            (8, _, _, 4) => Instruction::AddReg(x, y),
+            (8, _, _, 5) => Instruction::Sub(x, y),
+            (8, _, _, 6) => Instruction::Shr(x, y),
+            (8, _, _, 7) => Instruction::SubN(x, y),
+            (8, _, _, 0xe) => Instruction::Shl(x, y),
+            (9, _, _, 0) => Instruction::SkipNeqReg(x, y),
+            (0xa, _, _, _) => Instruction::SetI(nnn),
+            (0xb, _, _, _) => Instruction::JumpOffset(nnn),
+            (0xc, _, _, _) => Instruction::Random(x, low),
+            (0xd, _, _, _) => Instruction::Draw(x, y, b),
+            (0xe, _, 9, 0xe) => Instruction::SkipKeyDown(x),
+            (0xe, _, 0xa, 1) => Instruction::SkipKeyUp(x),
+            (0xf, _, 0, 7) => Instruction::LoadDelay(x),
+            (0xf, _, 0, 0xa) => Instruction::WaitKey(x),
+            (0xf, _, 1, 5) => Instruction::SetDelay(x),
+            (0xf, _, 1, 8) => Instruction::SetSound(x),
+            (0xf, _, 1, 0xe) => Instruction::AddI(x),
+            (0xf, _, 2, 9) => Instruction::LoadFont(x),
+            (0xf, _, 3, 3) => Instruction::StoreBcd(x),
+            (0xf, _, 5, 5) => Instruction::StoreRegisters(x),
+            (0xf, _, 6, 5) => Instruction::ReadRegisters(x),
+            _ => Instruction::Invalid(opcode),
+        }
+    }
+
+    /// Formats instructions with the usual CHIP-8 mnemonics, e.g. `LD V3, 0x0a` or
+    /// `DRW V0, V1, 5`. Opcodes that aren't instructions are shown as data.
+    impl fmt::Display for Instruction {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Instruction::ClearScreen => write!(f, "CLS"),
+                Instruction::Return => write!(f, "RET"),
+                Instruction::Sys(nnn) => write!(f, "SYS 0x{:03x}", nnn),
+                Instruction::Jump(nnn) => write!(f, "JP 0x{:03x}", nnn),
+                Instruction::Call(nnn) => write!(f, "CALL 0x{:03x}", nnn),
+                Instruction::SkipEqImm(x, nn) => write!(f, "SE V{:X}, 0x{:02x}", x, nn),
+                Instruction::SkipNeqImm(x, nn) => write!(f, "SNE V{:X}, 0x{:02x}", x, nn),
+                Instruction::SkipEqReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+                Instruction::LoadImm(x, nn) => write!(f, "LD V{:X}, 0x{:02x}", x, nn),
+                Instruction::AddImm(x, nn) => write!(f, "ADD V{:X}, 0x{:02x}", x, nn),
+                Instruction::LoadReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+                Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+                Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+                Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+                Instruction::AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+                Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+                Instruction::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+                Instruction::SubN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+                Instruction::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+                Instruction::SkipNeqReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+                Instruction::SetI(nnn) => write!(f, "LD I, 0x{:03x}", nnn),
+                Instruction::JumpOffset(nnn) => write!(f, "JP V0, 0x{:03x}", nnn),
+                Instruction::Random(x, nn) => write!(f, "RND V{:X}, 0x{:02x}", x, nn),
+                Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+                Instruction::SkipKeyDown(x) => write!(f, "SKP V{:X}", x),
+                Instruction::SkipKeyUp(x) => write!(f, "SKNP V{:X}", x),
+                Instruction::LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+                Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+                Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+                Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+                Instruction::AddI(x) => write!(f, "ADD I, V{:X}", x),
+                Instruction::LoadFont(x) => write!(f, "LD F, V{:X}", x),
+                Instruction::StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+                Instruction::StoreRegisters(x) => write!(f, "LD [I], V{:X}", x),
+                Instruction::ReadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+                Instruction::Invalid(opcode) => write!(f, "DW 0x{:04x}", opcode),
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+pub use lint::lint;
+pub use lint::report;
+pub use lint::Finding;
+
+pub mod lint {
+    use std::collections::BTreeMap;
+
+    use crate::emulator::{Chip8Result, analysis, cpu::{LOAD_ADDRESS, MAX_STACK_DEPTH, MEMORY_SIZE}, instruction::{self, Instruction}, rom::{self, RomFormat}};
+
+    /// Instructions followed after FX55, FX65 or ANNN when looking for what depends on them
+    const LOOKAHEAD: usize = 32;
+
+    /// Something in a ROM that may not work the same everywhere, or at all.
+    pub struct Finding {
+        /// The address of the instruction it is about, if any
+        pub addr: Option<usize>,
+        pub message: String,
+    }
+
+    /// Looks for constructs whose behavior depends on quirks, code that is never reached,
+    /// jumps out of the program, sprites read past the end of memory and calls nested too
+    /// deeply. Like the rest of the analysis, only code reachable without computed jumps
+    /// is checked, so some problems can go unnoticed.
+    pub fn lint(rom: &[u8]) -> Vec<Finding> {
+        let code: BTreeMap<usize, u16> = analysis::reachable_instructions(rom).into_iter().collect();
+        let end = LOAD_ADDRESS + rom.len();
+        let mut findings = Vec::new();
+        let mut found = |addr, message: String| findings.push(Finding { addr, message });
+
+        for (&addr, &opcode) in code.iter() {
+            let instruction = instruction::decode(opcode);
+            match instruction {
+                Instruction::Shr(x, y) | Instruction::Shl(x, y) if x != y => {
+                    found(Some(addr), format!("{:04X} shifts V{:X} on some platforms and V{:X} on others (shift_vy quirk)", opcode, y, x));
+                }
+                Instruction::StoreRegisters(_) | Instruction::ReadRegisters(_) => {
+                    let uses = following(&code, addr)
+                        .take_while(|(_, opcode)| !matches!(instruction::decode(*opcode), Instruction::SetI(_) | Instruction::LoadFont(_)))
+                        .find(|(_, opcode)| uses_i(*opcode));
+                    if let Some((next, next_opcode)) = uses {
+                        found(Some(addr), format!(
+                            "{:04X} may or may not advance I, which {:04X} at 0x{:03x} uses next (memory_increments_i quirk)",
+                            opcode, next_opcode, next
+                        ));
+                    }
+                }
+                Instruction::JumpOffset(nnn) if nnn & 0xf00 != 0 => {
+                    found(Some(addr), format!(
+                        "{:04X} adds V0 on some platforms and V{:X} on others (jump_vx quirk)", opcode, nnn >> 8
+                    ));
+                }
+                _ => {}
+            }
+
+            match instruction {
+                Instruction::Jump(nnn) | Instruction::Call(nnn) | Instruction::JumpOffset(nnn)
+                    if (nnn as usize) < LOAD_ADDRESS || nnn as usize >= end =>
+                {
+                    found(Some(addr), format!("{:04X} goes to 0x{:03x}, outside of the program", opcode, nnn));
+                }
+                Instruction::SetI(nnn) => {
+                    for (next, next_opcode) in following(&code, addr) {
+                        let len = match instruction::decode(next_opcode) {
+                            Instruction::Draw(_, _, n) => n as usize,
+                            Instruction::StoreBcd(_) => 3,
+                            Instruction::StoreRegisters(x) | Instruction::ReadRegisters(x) => x as usize + 1,
+                            _ => 0,
+                        };
+                        if nnn as usize + len > MEMORY_SIZE {
+                            found(Some(next), format!(
+                                "{:04X} accesses {} bytes at 0x{:03x}, set by {:04X} at 0x{:03x}, past the end of memory",
+                                next_opcode, len, nnn, opcode, addr
+                            ));
+                        }
+                        if sets_i(next_opcode) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (start, stop) in gaps(&code) {
+            found(Some(start), format!(
+                "0x{:03x}-0x{:03x} is never reached, it's either data or code only reached through BNNN", start, stop - 1
+            ));
+        }
+
+        match analysis::stack_depth(rom) {
+            Some(depth) if depth > MAX_STACK_DEPTH => {
+                found(None, format!("Subroutine calls nest {} deep, more than the {} the stack holds", depth, MAX_STACK_DEPTH));
+            }
+            Some(_) => {}
+            None => found(None, "A subroutine can call itself, so the stack may overflow".to_string()),
+        }
+
+        findings.sort_by_key(|finding| finding.addr);
+        findings
+    }
+
+    /// Lints the program in a file and prints what was found.
+    pub fn report(program: &str, format: Option<RomFormat>) -> Chip8Result<()> {
+        let rom = rom::load(program, format)?.program;
+        let findings = lint(&rom);
+        for finding in findings.iter() {
+            match finding.addr {
+                Some(addr) => println!("0x{:03x}: {}", addr, finding.message),
+                None => println!("{}", finding.message),
+            }
+        }
+
+        if let Some(depth) = analysis::stack_depth(&rom) {
+            println!("Subroutine calls nest up to {} deep", depth);
+        }
+        println!("{} finding(s) in {}", findings.len(), program);
+        Ok(())
+    }
+
+    /// The instructions executed after the one at `addr`, for as long as there's no branch.
+    fn following(code: &BTreeMap<usize, u16>, addr: usize) -> impl Iterator<Item = (usize, u16)> + '_ {
+        let mut addr = addr;
+        (0..LOOKAHEAD).map_while(move |_| {
+            let next = match analysis::successors(addr, *code.get(&addr)?)[..] {
+                [next] => next,
+                _ => return None,
+            };
+            addr = next;
+            Some((next, *code.get(&next)?))
+        })
+    }
+
+    fn uses_i(opcode: u16) -> bool {
+        matches!(instruction::decode(opcode),
+            Instruction::Draw(..) | Instruction::AddI(_) | Instruction::StoreBcd(_)
+                | Instruction::StoreRegisters(_) | Instruction::ReadRegisters(_))
+    }
+
+    fn sets_i(opcode: u16) -> bool {
+        matches!(instruction::decode(opcode),
+            Instruction::SetI(_) | Instruction::LoadFont(_) | Instruction::AddI(_)
+                | Instruction::StoreRegisters(_) | Instruction::ReadRegisters(_))
+    }
+
+    /// Ranges of the program between reachable instructions that are never reached.
+    fn gaps(code: &BTreeMap<usize, u16>) -> Vec<(usize, usize)> {
+        let mut gaps = Vec::new();
+        let mut covered = match code.keys().next() {
+            Some(first) => *first,
+            None => return gaps,
+        };
+        for addr in code.keys() {
+            if *addr > covered {
+                gaps.push((covered, *addr));
+            }
+            covered = covered.max(addr + 2);
+        }
+        gaps
+    }
+}
@@ -1,47 +1,667 @@
-mod cpu;
-mod ui;
-mod input;
+pub mod cpu;
+pub mod instruction;
+pub mod ui;
+pub mod input;
+pub mod config;
+pub mod storage;
+pub mod state;
+pub mod slots;
+pub mod rom;
+pub mod bundle;
+pub mod settings;
+pub mod romdb;
+pub mod audio;
+pub mod variants;
+pub mod quirks;
+pub mod analysis;
+pub mod debugger;
+pub mod hooks;
+pub mod dump;
+pub mod dialog;
+pub mod gamepad;
+pub mod batch;
+pub mod plugin;
+pub mod heatmap;
+pub mod expr;
+pub mod lint;
+pub mod stats;
+pub mod remap;
+pub mod replay;
+pub mod engine;
+pub mod clock;
+pub mod signal;
+pub mod pacing;
+pub mod pause;
 
 pub type Chip8Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 pub type Chip8Result<T> = Result<T, Chip8Error>;
 
-use std::{thread, time::Duration};
-use sdl2::event::Event;
+use std::{fs, path::PathBuf, thread, time::{Duration, Instant}};
+use sdl2::{Sdl, event::{Event, WindowEvent}, keyboard::Keycode};
 use cpu::CPU;
+use engine::Chip8Core;
 use ui::Screen;
-use input::Keyboard;
+use input::{AutoFire, Input, InputSource, Keyboard, MacroPlayer};
+use config::{AutoSave, Command, Config};
+use state::SaveState;
+use slots::{SlotMenu, SlotMenuAction, SlotMenuMode};
+use settings::Settings;
+use rom::RomHashes;
+use romdb::RomDatabase;
+use audio::{Buzzer, WavRecorder};
+use variants::{Detection, Platform};
+use quirks::Quirks;
+use debugger::Debugger;
+use gamepad::Rumble;
+use plugin::Plugin;
+use heatmap::HeatMap;
+use remap::{RemapAction, RemapMenu};
+use replay::{Replay, ReplayPlayer, ReplayRecorder};
+use clock::TimerClock;
+use dump::Trace;
+use pacing::FramePacing;
+use pause::{PauseAction, PauseMenu};
 
-pub fn run(file: &str) -> Chip8Result<()> {
-    let sdl_context = sdl2::init()?;
-    let title = format!("{} - {}", "rschip8", file);
-    let mut screen = Screen::new(&sdl_context, &title)?;
-    let mut kb = Keyboard::new();
-    let mut cpu = CPU::from_file(file)?;
+const RESET_KEY: Keycode = Keycode::F2;
+
+pub fn run(config: &Config) -> Chip8Result<()> {
+    run_with_plugins(config, Vec::new())
+}
+
+/// How a program stopped running
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ending {
+    /// The window was closed or the player quit
+    Quit,
+    /// Attract moved on to the next ROM
+    TimeUp,
+}
+
+/// Runs like `run`, with `plugins` following the program as it runs.
+pub fn run_with_plugins(config: &Config, mut plugins: Vec<Box<dyn Plugin>>) -> Chip8Result<()> {
+    match config.command {
+        Command::Run => {}
+        Command::ListPlatforms => {
+            list_platforms();
+            return Ok(());
+        }
+        Command::ListQuirks => {
+            list_quirks();
+            return Ok(());
+        }
+        Command::TestDir => {
+            let dir = config.program.as_deref().ok_or("No ROM directory given")?;
+            return batch::test_dir(dir, config.cycles, &config.settings);
+        }
+        Command::Lint => {
+            let program = config.program.as_deref().ok_or("No program given")?;
+            return lint::report(program, config.rom_format);
+        }
+        Command::Headless => {
+            let program = config.program.as_deref().ok_or("No program given")?;
+            return batch::run_headless(program, config.rom_format, config.cycles, &config.settings);
+        }
+        Command::Stats => {
+            let program = config.program.as_deref().ok_or("No program given")?;
+            return stats::report(program, config.rom_format);
+        }
+        Command::Attract => {
+            let dir = config.program.as_deref().ok_or("No ROM directory given")?;
+            return attract(config, dir, &sdl2::init()?, &mut plugins);
+        }
+    }
+
+    let program = match &config.program {
+        Some(program) => program.clone(),
+        None => match dialog::pick_program() {
+            Some(program) => program,
+            None => return Ok(()),
+        },
+    };
+    let replay = config.replay.as_deref().map(Replay::load).transpose()?;
+    run_program(config, &program, &sdl2::init()?, &mut None, &mut plugins, replay, None)?;
+    Ok(())
+}
+
+/// Shows the ROMs in `dir` one after another for `config.attract_time` seconds each, playing
+/// back the latest replay of each ROM that has one, until a key is pressed. The player then
+/// gets the ROM on screen from its start. ROMs that fail are skipped. They're all shown in
+/// the same window.
+fn attract(config: &Config, dir: &str, sdl_context: &Sdl, plugins: &mut [Box<dyn Plugin>]) -> Chip8Result<()> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("There are no ROMs in {}", dir).into());
+    }
+
+    let time = Duration::from_secs(config.attract_time);
+    let mut screen = None;
+    let mut failures = 0;
+    for path in paths.iter().cycle() {
+        let program = path.to_string_lossy().into_owned();
+        let demo = match demo_replay(config, &program) {
+            Ok(demo) => demo,
+            Err(e) => {
+                eprintln!("Ignoring the replay of {}: {}", program, e);
+                None
+            }
+        };
+        match run_program(config, &program, sdl_context, &mut screen, plugins, demo, Some(time)) {
+            Ok(Ending::Quit) => return Ok(()),
+            Ok(Ending::TimeUp) => failures = 0,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", program, e);
+                failures += 1;
+                if failures == paths.len() {
+                    return Err(format!("None of the ROMs in {} could be run", dir).into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The latest replay saved for a program, to show as a demo.
+fn demo_replay(config: &Config, program: &str) -> Chip8Result<Option<Replay>> {
+    let rom = rom::load(program, config.rom_format)?.program;
+    match Replay::latest(&RomHashes::of(&rom).sha1)? {
+        Some(path) => Ok(Some(Replay::load(&path.to_string_lossy())?)),
+        None => Ok(None),
+    }
+}
+
+/// Runs `program` in a window until it's closed, reusing `screen` if it's already open and
+/// leaving it there otherwise. With `attract`, it stops after that long, unless a key is
+/// pressed first, which resets the program and hands it over to the player.
+fn run_program(config: &Config, program: &str, sdl_context: &Sdl, screen: &mut Option<Screen>,
+               plugins: &mut [Box<dyn Plugin>], replay: Option<Replay>,
+               attract: Option<Duration>) -> Chip8Result<Ending> {
+    let loaded = rom::load(program, config.rom_format)?;
+    if let Some(title) = &loaded.title {
+        eprintln!("{}{}", title, loaded.author.as_ref().map(|author| format!(" by {}", author)).unwrap_or_default());
+    }
+    let rom = loaded.program;
+    let mut cpu = CPU::from_bytes(&rom).map_err(|e| format!("Could not load {}: {}", program, e))?;
+    let hashes = RomHashes::of(&rom);
+    let rom_hash = &hashes.sha1;
+    eprintln!("Loaded {} ({} bytes, {})", program, rom.len(), hashes);
+
+    let mut rom_db = RomDatabase::load()?;
+    for warning in rom_db.verify(&hashes, program) {
+        eprintln!("Warning: {}", warning);
+    }
+    rom_db.remember(&hashes, program)?;
+
+    // Settings given on the command line are remembered for the next time the ROM is loaded,
+    // unless they're for every ROM attract shows. Both take precedence over the ones
+    // suggested by the file the ROM came in, which are only used for this session.
+    let remembered = Settings::load(rom_hash)?.merge(config.settings.clone());
+    if !config.settings.is_empty() && attract.is_none() {
+        remembered.save(rom_hash)?;
+    }
+    let settings = loaded.settings.merge(remembered);
+    // Unless a platform was chosen, it's picked based on the extension opcodes the ROM uses
+    let platform = match settings.platform()? {
+        Some(platform) => platform,
+        None => match Detection::scan(&rom) {
+            Some(detection) => {
+                let (addr, foreign) = &detection.evidence[0];
+                eprintln!("Detected {} opcodes ({:04X} at 0x{:0x} and {} more), using the {} platform",
+                    detection.platform, foreign.opcode, addr, detection.evidence.len() - 1,
+                    detection.platform.name());
+                eprintln!("Warning: rschip8 only emulates CHIP-8 instructions, the program will stop at the first {} one",
+                    detection.platform);
+                detection.platform
+            }
+            None => Platform::Chip8,
+        },
+    };
+    let mut quirks = platform.quirks();
+    settings.apply_quirks(&mut quirks)?;
+    cpu.set_platform(platform);
+    cpu.set_quirks(quirks);
+    cpu.set_display_height(settings.display_height()?);
+
+    let mut speed = settings.speed();
+    let mut cycles_per_iteration = speed as f64 / 600.0;
+    if let Some(replay) = &replay {
+        if replay.rom_sha1 != *rom_hash {
+            return Err("The replay was recorded with a different program".into());
+        }
+    }
+    let mut cycles_per_frame = (speed / 60).max(1);
+
+    let title = format!("{} - {} [{:08x}]", "rschip8", loaded.title.as_deref().unwrap_or(program), hashes.crc32);
+    let screen = match screen {
+        Some(screen) => {
+            screen.reopen(&title, settings.palette()?, settings.display_height()?)?;
+            screen
+        }
+        None => screen.insert(Screen::new(sdl_context, &title, settings.palette()?, settings.display_height()?)?),
+    };
+    let mut kb = Keyboard::new(settings.keymap()?, settings.macros()?, settings.autofire()?);
+    let mut macros = MacroPlayer::new();
+    let mut autofire = AutoFire::new();
+    let mut input = Input::new();
+    let mut buzzer = if config.mute { None } else { Some(Buzzer::new(sdl_context)?) };
+    let mut rumble = if config.rumble { Some(Rumble::new(sdl_context)?) } else { None };
+    let mut recorder = match &config.record_audio {
+        Some(filename) => Some(WavRecorder::create(filename)?),
+        None => None,
+    };
+
+    // Attract always shows a ROM from its start
+    if config.auto_save != AutoSave::Off && replay.is_none() && attract.is_none() {
+        let path = SaveState::auto_save_path(rom_hash)?;
+        let state = SaveState::load(&path).unwrap_or_else(|e| {
+            eprintln!("Ignoring auto-save {}: {}", path.display(), e);
+            None
+        });
+        if let Some(state) = state {
+            let resume = config.auto_save == AutoSave::Resume || screen.ask(
+                "Resume",
+                &format!("Resume {} from where you left off on {} (UTC)?",
+                    program, state.formatted_timestamp())
+            )?;
+            if resume {
+                state.restore(&mut cpu);
+                screen.show(cpu.display());
+            }
+        }
+    }
 
     let mut event_pump = sdl_context.event_pump()?;
-    let mut timer_60hz = 0;
-    'emulator: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} => {
-                    break 'emulator;
+    let mut timer_clock = TimerClock::new(sdl_context.timer()?);
+    // Timer ticks are due in real time but only take effect before the next instruction,
+    // which is the only thing that could tell
+    let mut timer_ticks = 0;
+    let mut replay_player = None;
+    if let Some(replay) = replay {
+        replay_player = Some(ReplayPlayer::start(replay, &mut cpu, &mut input));
+        screen.show(cpu.display());
+    }
+    let mut replay_recorder = ReplayRecorder::new();
+    let mut frame_60hz = 0;
+    let mut turbo = false;
+    let mut slowmo = false;
+    let mut pending_cycles = 0.0;
+    let mut slot_menu: Option<SlotMenu> = None;
+    let mut heat_map: Option<HeatMap> = None;
+    let mut pacing: Option<FramePacing> = None;
+    let mut remap_menu: Option<RemapMenu> = None;
+    let mut pause_menu: Option<PauseMenu> = None;
+    let mut debugger = Debugger::load(rom_hash)?;
+    let mut trace = Trace::new();
+    signal::install_dump_handler()?;
+    let mut attract_until = attract.map(|time| Instant::now() + time);
+    if config.start_paused {
+        debugger.pause(&cpu, "Paused before the first instruction");
+    }
+    // The loop runs in a closure so the machine can still be dumped when it fails
+    let mut emulate = || -> Chip8Result<Ending> {
+        'emulator: loop {
+            if let Some(until) = attract_until {
+                if Instant::now() >= until {
+                    return Ok(Ending::TimeUp);
+                }
+            }
+
+            let mut reset = false;
+            for event in event_pump.poll_iter() {
+                match event {
+                    Event::Quit {..} => {
+                        break 'emulator;
+                    }
+                    // The frame is only drawn when it changes, so it has to be redrawn when it's uncovered
+                    Event::Window { win_event: WindowEvent::Exposed, .. } => screen.display_buffer.is_dirty = true,
+                    Event::KeyDown { .. } if attract_until.is_some() => {
+                        attract_until = None;
+                        reset = true;
+                    }
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        if let Some(rumble) = &mut rumble { rumble.connect(which); }
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        if let Some(rumble) = &mut rumble { rumble.disconnect(which); }
+                    }
+                    Event::KeyDown { keycode: Some(code), .. } if pause_menu.is_some() => {
+                        let action = pause_menu.as_mut().unwrap().handle_key(code);
+                        // Every action but changing the speed or the look closes the menu
+                        if !matches!(action, PauseAction::None | PauseAction::Speed(_) | PauseAction::Look { .. }) {
+                            remember_pause_changes(&pause_menu.take().unwrap(), rom_hash)?;
+                        }
+                        match action {
+                            PauseAction::None | PauseAction::Close => {}
+                            PauseAction::Reset => reset = true,
+                            PauseAction::SaveState => {
+                                slot_menu = Some(SlotMenu::open(SlotMenuMode::Save, rom_hash)?);
+                            }
+                            PauseAction::LoadState => {
+                                slot_menu = Some(SlotMenu::open(SlotMenuMode::Load, rom_hash)?);
+                            }
+                            PauseAction::Speed(new_speed) => {
+                                speed = new_speed;
+                                cycles_per_iteration = speed as f64 / 600.0;
+                                cycles_per_frame = (speed / 60).max(1);
+                            }
+                            PauseAction::Look { inverted, lcd } => screen.set_look(inverted, lcd),
+                            PauseAction::RemapKeys => {
+                                input.release_all(InputSource::Keyboard);
+                                remap_menu = Some(RemapMenu::open(kb.keymap()));
+                            }
+                            PauseAction::Quit => break 'emulator,
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(code), .. } if slot_menu.is_some() => {
+                        let menu = slot_menu.as_mut().unwrap();
+                        match menu.handle_key(code) {
+                            SlotMenuAction::None => {}
+                            SlotMenuAction::Close => slot_menu = None,
+                            SlotMenuAction::Save(slot) => {
+                                let state = SaveState::capture(&cpu);
+                                if let Err(e) = state.save(&SaveState::slot_path(rom_hash, slot)?) {
+                                    eprintln!("Could not save state: {}", e);
+                                }
+                                slot_menu = None;
+                            }
+                            SlotMenuAction::Load(slot) => {
+                                if let Some(state) = &menu.slots[slot] {
+                                    state.restore(&mut cpu);
+                                    screen.show(cpu.display());
+                                    replay_recorder.restart();
+                                    replay_player = None;
+                                    input.hand_back();
+                                }
+                                slot_menu = None;
+                            }
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(code), repeat: false, .. } if remap_menu.is_some() => {
+                        match remap_menu.as_mut().unwrap().handle_key(code) {
+                            RemapAction::None => {}
+                            RemapAction::Close => remap_menu = None,
+                            RemapAction::Done(keymap) => {
+                                // Only the keymap is saved, so settings from the command line aren't remembered by accident
+                                let mut remembered = Settings::load(rom_hash)?;
+                                remembered.keymap = keymap.clone();
+                                remembered.save(rom_hash)?;
+                                kb.set_keymap(Settings { keymap, ..Settings::default() }.keymap()?);
+                                remap_menu = None;
+                            }
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(remap::REMAP_KEY), repeat: false, .. } => {
+                        input.release_all(InputSource::Keyboard);
+                        remap_menu = Some(RemapMenu::open(kb.keymap()));
+                    }
+                    Event::KeyDown { keycode: Some(pause::PAUSE_MENU_KEY), repeat: false, .. } => {
+                        input.release_all(InputSource::Keyboard);
+                        pause_menu = Some(PauseMenu::open(speed, screen.palette()));
+                    }
+                    Event::KeyDown { keycode: Some(slots::SAVE_MENU_KEY), repeat: false, .. } => {
+                        slot_menu = Some(SlotMenu::open(SlotMenuMode::Save, rom_hash)?);
+                    }
+                    Event::KeyDown { keycode: Some(slots::LOAD_MENU_KEY), repeat: false, .. } => {
+                        slot_menu = Some(SlotMenu::open(SlotMenuMode::Load, rom_hash)?);
+                    }
+                    Event::KeyDown { keycode: Some(ui::INVERT_KEY), repeat: false, .. } => screen.toggle_inverted(),
+                    Event::KeyDown { keycode: Some(ui::LCD_KEY), repeat: false, .. } => screen.toggle_lcd(),
+                    Event::KeyDown { keycode: Some(RESET_KEY), repeat: false, .. } => reset = true,
+                    Event::KeyDown { keycode: Some(replay::EXPORT_REPLAY_KEY), repeat: false, .. } => {
+                        if let Some(replay) = replay_recorder.export(rom_hash) {
+                            let path = Replay::path(rom_hash)?;
+                            match replay.save(&path) {
+                                Ok(()) => eprintln!("Saved a replay of the last {} seconds to {}",
+                                    replay.seconds(), path.display()),
+                                Err(e) => eprintln!("Could not save replay: {}", e),
+                            }
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(heatmap::HEAT_MAP_KEY), repeat: false, .. } => {
+                        heat_map = match heat_map {
+                            Some(_) => None,
+                            None => Some(HeatMap::new(cpu.memory().len())),
+                        };
+                    }
+                    Event::KeyDown { keycode: Some(pacing::PACING_KEY), repeat: false, .. } => {
+                        pacing = match pacing {
+                            Some(_) => None,
+                            None => Some(FramePacing::new()),
+                        };
+                    }
+                    Event::KeyDown { keycode: Some(debugger::PAUSE_KEY), repeat: false, .. } => {
+                        if debugger.is_paused() {
+                            debugger.resume(&cpu);
+                        } else {
+                            debugger.pause(&cpu, "Paused");
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(code), .. } if code == config.turbo_key => {
+                        turbo = true;
+                    }
+                    Event::KeyUp { keycode: Some(code), .. } if code == config.turbo_key => {
+                        turbo = false;
+                    }
+                    Event::KeyDown { keycode: Some(code), repeat: false, .. } if code == config.slowmo_key => {
+                        slowmo = !slowmo;
+                    }
+                    Event::KeyDown { keycode: Some(code), repeat, .. } => {
+                        if let Some(m) = kb.macro_for(code) {
+                            if !repeat { macros.start(m); }
+                        } else if let Some(key) = kb.key(code) {
+                            // Keys with autofire on are pressed by the autofire rather than held
+                            match kb.autofire_rate(code) {
+                                Some(rate) => autofire.hold(key, rate),
+                                None => input.set(InputSource::Keyboard, key, true),
+                            }
+                        }
+                    }
+                    Event::KeyUp { keycode: Some(code), .. } => {
+                        if let Some(key) = kb.key(code) {
+                            input.set(InputSource::Keyboard, key, false);
+                            autofire.release(key);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if reset {
+                cpu.reset();
+                screen.show(cpu.display());
+                replay_recorder.restart();
+                replay_player = None;
+                input.hand_back();
+            }
+
+            debugger.handle_commands(&mut cpu);
+            signal::dump_if_requested(&cpu, &trace);
+
+            // Emulation is suspended while a menu is open
+            if pause_menu.is_some() || remap_menu.is_some() || slot_menu.is_some() {
+                if let Some(buzzer) = &mut buzzer { buzzer.update(false); }
+                if let Some(rumble) = &mut rumble { rumble.update(false); }
+                if frame_60hz == 0 {
+                    if let Some(menu) = &pause_menu {
+                        screen.draw_pause_menu(menu)?;
+                    } else if let Some(menu) = &remap_menu {
+                        screen.draw_remap_menu(menu)?;
+                    } else if let Some(menu) = &slot_menu {
+                        screen.draw_slot_menu(menu)?;
+                    }
+                }
+                timer_clock.skip();
+                thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+                frame_60hz = (frame_60hz + 1) % 10;
+                continue;
+            }
+
+            // The speed decides how many instructions are due on this loop iteration;
+            // drawing stays at the real 60Hz rate, skipping or repeating frames as needed.
+            // Turbo takes precedence over slow motion while it is held.
+            let rate = if turbo {
+                settings.turbo_factor() as f64
+            } else if slowmo {
+                settings.slowmo_factor()
+            } else {
+                1.0
+            };
+            pending_cycles += cycles_per_iteration * rate;
+            // A replay brings its own timer ticks
+            if debugger.is_paused() || replay_player.is_some() {
+                timer_clock.skip();
+            } else {
+                timer_ticks += timer_clock.due(rate);
+            }
+            while pending_cycles >= 1.0 {
+                if !debugger.before_instruction(&cpu) {
+                    pending_cycles = 0.0;
+                    break;
+                }
+
+                let instruction = cpu.next_instruction();
+                if let Some(player) = &mut replay_player {
+                    match player.before_instruction(&mut input) {
+                        Some(ticks) => timer_ticks = ticks,
+                        None => {
+                            eprintln!("Replay finished");
+                            replay_player = None;
+                            input.hand_back();
+                        }
+                    }
+                }
+                input.apply(&mut cpu);
+                replay_recorder.before_instruction(&cpu, timer_ticks);
+                if let Some(heat_map) = &mut heat_map { heat_map.record(&cpu, instruction); }
+                trace.record(&cpu);
+                if let Some(pacing) = &mut pacing { pacing.add_cycle(); }
+                for _ in 0..timer_ticks {
+                    cpu.tick_timers();
+                    if let Some(buzzer) = &mut buzzer { buzzer.update(cpu.is_sound_playing()); }
+                    if let Some(rumble) = &mut rumble { rumble.update(cpu.is_sound_playing()); }
+                    if let Some(recorder) = &mut recorder { recorder.record_frame(cpu.is_sound_playing())?; }
+                }
+                timer_ticks = 0;
+                let event = cpu.step()?;
+                if let Some(cpu::Event::DrawRequested) = event {
+                    screen.show(cpu.display());
+                }
+                if let Some(event) = event {
+                    for plugin in plugins.iter_mut() { plugin.on_event(&cpu, event); }
+                }
+                debugger.after_instruction(&cpu, instruction);
+                pending_cycles -= 1.0;
+            }
+            if debugger.is_paused() {
+                if let Some(buzzer) = &mut buzzer { buzzer.update(false); }
+                if let Some(rumble) = &mut rumble { rumble.update(false); }
+            }
+            if frame_60hz == 0 && !debugger.is_paused() {
+                macros.tick(&mut input);
+                autofire.tick(&mut input);
+                for plugin in plugins.iter_mut() {
+                    plugin.on_frame(&cpu);
+                    for (key, pressed) in plugin.input(&cpu) {
+                        input.set(InputSource::Plugin, key, pressed);
+                    }
                 }
-                Event::KeyDown { keycode: Some(code), .. } => {
-                    kb.key_down(code);
+            }
+            if frame_60hz == 0 {
+                if settings.run_ahead() > 0 && !debugger.is_paused() {
+                    screen.show(run_ahead(&cpu, settings.run_ahead(), cycles_per_frame).display());
                 }
-                Event::KeyUp { keycode: Some(code), .. } => {
-                    kb.key_up(code);
+                if let Some(pacing) = &mut pacing { pacing.end_frame(); }
+                screen.fade();
+                match (&mut heat_map, &pacing) {
+                    (Some(heat_map), _) => {
+                        screen.draw_heat_map(heat_map, cpu.memory())?;
+                        if !debugger.is_paused() { heat_map.cool(); }
+                    }
+                    (None, Some(pacing)) => screen.draw_pacing(pacing, cycles_per_frame)?,
+                    (None, None) => screen.draw()?,
                 }
-                _ => {}
             }
+
+            let sleep_start = Instant::now();
+            thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+            if let Some(pacing) = &mut pacing { pacing.add_sleep(sleep_start.elapsed()); }
+            frame_60hz = (frame_60hz + 1) % 10;
         }
+        Ok(Ending::Quit)
+    };
+    let result = emulate();
 
-        cpu.tick(&kb, &mut screen.display_buffer, timer_60hz == 0)?;
-        if timer_60hz == 0 { screen.draw()?; }
+    if let Some(path) = &config.dump_on_exit {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        dump::write_dump(&cpu, error.as_deref(), Some(&trace), path)?;
+    }
+    let ending = result?;
+    // The window may be closed while the menu is still open
+    if let Some(menu) = &pause_menu {
+        remember_pause_changes(menu, rom_hash)?;
+    }
 
-        thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
-        timer_60hz = (timer_60hz + 1) % 10;
+    if config.auto_save != AutoSave::Off && attract_until.is_none() {
+        SaveState::capture(&cpu).save(&SaveState::auto_save_path(rom_hash)?)?;
     }
 
+    Ok(ending)
+}
+
+/// Remembers the speed and look picked in the pause menu for the ROM, like the keymap,
+/// once the menu is closed.
+fn remember_pause_changes(menu: &PauseMenu, rom_hash: &str) -> Chip8Result<()> {
+    let changes = menu.changes();
+    if !changes.is_empty() {
+        Settings::load(rom_hash)?.merge(changes).save(rom_hash)?;
+    }
     Ok(())
 }
+
+fn list_platforms() {
+    for platform in Platform::ALL.iter() {
+        let quirks = platform.quirks();
+        let enabled: Vec<&str> = Quirks::NAMES.iter()
+            .cloned()
+            .filter(|name| quirks.get(name).unwrap_or(false))
+            .collect();
+        let enabled = if enabled.is_empty() { "none".to_string() } else { enabled.join(", ") };
+        println!("{:<10}{:<12}quirks: {}", platform.name(), platform, enabled);
+    }
+}
+
+fn list_quirks() {
+    print!("{:<21}", "");
+    for platform in Platform::ALL.iter() {
+        print!("{:<10}", platform.name());
+    }
+    println!();
+    for name in Quirks::NAMES.iter() {
+        print!("{:<21}", name);
+        for platform in Platform::ALL.iter() {
+            let on = platform.quirks().get(name).unwrap_or(false);
+            print!("{:<10}", if on { "on" } else { "off" });
+        }
+        println!("{}", Quirks::description(name));
+    }
+}
+
+/// Emulates `frames` frames on a copy of the machine with the keys currently held, so the
+/// display reacts to input as soon as the program would. The copy is thrown away, which rolls
+/// back the speculation once the real machine catches up with different input.
+fn run_ahead<C: Chip8Core + Clone>(cpu: &C, frames: u32, cycles_per_frame: u32) -> C {
+    let mut ahead = cpu.clone();
+    for _ in 0..frames {
+        ahead.tick_timers();
+        for _ in 0..cycles_per_frame {
+            // Errors are left for the real machine to run into
+            if ahead.step().is_err() {
+                return ahead;
+            }
+        }
+    }
+    ahead
+}
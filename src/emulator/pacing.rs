@@ -0,0 +1,91 @@
+pub use pacing::FramePacing;
+pub use pacing::FrameSample;
+pub use pacing::PACING_KEY;
+pub use pacing::HISTORY;
+
+pub mod pacing {
+    use std::{collections::VecDeque, time::{Duration, Instant}};
+    use sdl2::keyboard::Keycode;
+
+    pub const PACING_KEY: Keycode = Keycode::F4;
+
+    /// Frames kept for the graph
+    pub const HISTORY: usize = 128;
+
+    /// How a frame went: the time it really took, the part of it spent sleeping and the
+    /// instructions executed.
+    #[derive(Clone, Copy)]
+    pub struct FrameSample {
+        pub frame: Duration,
+        pub sleep: Duration,
+        pub cycles: u32,
+    }
+
+    /// Measures the last frames, to tell whether stutter comes from the host, when frames
+    /// take longer than they sleep for, or from the settings, when fewer instructions
+    /// are executed than the speed asks for.
+    pub struct FramePacing {
+        samples: VecDeque<FrameSample>,
+        frame_start: Instant,
+        sleep: Duration,
+        cycles: u32,
+    }
+
+    impl FramePacing {
+        pub fn new() -> FramePacing {
+            FramePacing {
+                samples: VecDeque::with_capacity(HISTORY),
+                frame_start: Instant::now(),
+                sleep: Duration::from_secs(0),
+                cycles: 0,
+            }
+        }
+
+        pub fn add_sleep(&mut self, sleep: Duration) {
+            self.sleep += sleep;
+        }
+
+        pub fn add_cycle(&mut self) {
+            self.cycles += 1;
+        }
+
+        /// Records the frame that just ended and starts the next one.
+        pub fn end_frame(&mut self) {
+            if self.samples.len() == HISTORY {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(FrameSample {
+                frame: self.frame_start.elapsed(),
+                sleep: self.sleep,
+                cycles: self.cycles,
+            });
+            self.frame_start = Instant::now();
+            self.sleep = Duration::from_secs(0);
+            self.cycles = 0;
+        }
+
+        /// The recorded frames, oldest first
+        pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+            self.samples.iter()
+        }
+
+        /// The average of the recorded frames, if there are any.
+        pub fn average(&self) -> Option<FrameSample> {
+            let count = self.samples.len() as u32;
+            if count == 0 {
+                return None;
+            }
+            Some(FrameSample {
+                frame: self.samples.iter().map(|sample| sample.frame).sum::<Duration>() / count,
+                sleep: self.samples.iter().map(|sample| sample.sleep).sum::<Duration>() / count,
+                cycles: self.samples.iter().map(|sample| sample.cycles).sum::<u32>() / count,
+            })
+        }
+    }
+
+    impl Default for FramePacing {
+        fn default() -> FramePacing {
+            FramePacing::new()
+        }
+    }
+}
@@ -0,0 +1,149 @@
+pub use pause::PauseMenu;
+pub use pause::PauseAction;
+pub use pause::PauseItem;
+pub use pause::PAUSE_MENU_KEY;
+pub use pause::ITEMS;
+
+pub mod pause {
+    use sdl2::keyboard::Keycode;
+
+    use crate::emulator::settings::{Palette, Settings};
+
+    pub const PAUSE_MENU_KEY: Keycode = Keycode::Escape;
+
+    /// The speeds the menu steps through, in instructions per second
+    const SPEEDS: [u32; 10] = [60, 120, 300, 450, 600, 900, 1200, 1800, 3000, 6000];
+    /// The looks the menu cycles through, as whether the colors are inverted and whether
+    /// the display looks like an LCD
+    const LOOKS: [(bool, bool); 4] = [(false, false), (true, false), (false, true), (true, true)];
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum PauseItem {
+        Resume,
+        Reset,
+        SaveState,
+        LoadState,
+        Speed,
+        Look,
+        RemapKeys,
+        Quit,
+    }
+
+    pub const ITEMS: [PauseItem; 8] = [
+        PauseItem::Resume,
+        PauseItem::Reset,
+        PauseItem::SaveState,
+        PauseItem::LoadState,
+        PauseItem::Speed,
+        PauseItem::Look,
+        PauseItem::RemapKeys,
+        PauseItem::Quit,
+    ];
+
+    pub enum PauseAction {
+        None,
+        Close,
+        Reset,
+        SaveState,
+        LoadState,
+        Speed(u32),
+        Look { inverted: bool, lcd: bool },
+        RemapKeys,
+        Quit,
+    }
+
+    /// The menu opened with Escape, which reaches the features that otherwise have hotkeys
+    /// of their own. Emulation is suspended while it's open.
+    pub struct PauseMenu {
+        pub selected: usize,
+        pub speed: u32,
+        look: usize,
+        opened_speed: u32,
+        opened_look: usize,
+    }
+
+    impl PauseMenu {
+        pub fn open(speed: u32, palette: Palette) -> PauseMenu {
+            let look = LOOKS.iter().position(|look| *look == (palette.inverted, palette.lcd)).unwrap_or(0);
+            PauseMenu { selected: 0, speed, look, opened_speed: speed, opened_look: look }
+        }
+
+        /// The speed and look changed since the menu was opened, as settings to remember
+        /// for the ROM. Both are left unset when they weren't changed.
+        pub fn changes(&self) -> Settings {
+            let mut changes = Settings::default();
+            if self.speed != self.opened_speed {
+                changes.speed = Some(self.speed);
+            }
+            if self.look != self.opened_look {
+                let (inverted, lcd) = LOOKS[self.look];
+                changes.invert = Some(inverted);
+                changes.lcd = Some(lcd);
+            }
+            changes
+        }
+
+        /// The name of the current look, e.g. `LCD INVERTED`
+        pub fn look_name(&self) -> &'static str {
+            match LOOKS[self.look] {
+                (false, false) => "NORMAL",
+                (true, false) => "INVERTED",
+                (false, true) => "LCD",
+                (true, true) => "LCD INVERTED",
+            }
+        }
+
+        /// Up and Down move between items and Enter picks one. Left and Right change the
+        /// speed and the look, which take effect right away.
+        pub fn handle_key(&mut self, code: Keycode) -> PauseAction {
+            match code {
+                Keycode::Escape => PauseAction::Close,
+                Keycode::Up => {
+                    self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                    PauseAction::None
+                }
+                Keycode::Down => {
+                    self.selected = (self.selected + 1) % ITEMS.len();
+                    PauseAction::None
+                }
+                Keycode::Left => self.change(false),
+                Keycode::Right => self.change(true),
+                Keycode::Return | Keycode::KpEnter => match ITEMS[self.selected] {
+                    PauseItem::Resume => PauseAction::Close,
+                    PauseItem::Reset => PauseAction::Reset,
+                    PauseItem::SaveState => PauseAction::SaveState,
+                    PauseItem::LoadState => PauseAction::LoadState,
+                    PauseItem::Speed => PauseAction::None,
+                    PauseItem::Look => self.change(true),
+                    PauseItem::RemapKeys => PauseAction::RemapKeys,
+                    PauseItem::Quit => PauseAction::Quit,
+                },
+                _ => PauseAction::None,
+            }
+        }
+
+        /// Steps the selected item's value forward or back, if it has one.
+        fn change(&mut self, forward: bool) -> PauseAction {
+            match ITEMS[self.selected] {
+                PauseItem::Speed => {
+                    self.speed = if forward {
+                        SPEEDS.iter().cloned().find(|speed| *speed > self.speed).unwrap_or(self.speed)
+                    } else {
+                        SPEEDS.iter().rev().cloned().find(|speed| *speed < self.speed).unwrap_or(self.speed)
+                    };
+                    PauseAction::Speed(self.speed)
+                }
+                PauseItem::Look => {
+                    self.look = if forward {
+                        (self.look + 1) % LOOKS.len()
+                    } else {
+                        (self.look + LOOKS.len() - 1) % LOOKS.len()
+                    };
+                    let (inverted, lcd) = LOOKS[self.look];
+                    PauseAction::Look { inverted, lcd }
+                }
+                _ => PauseAction::None,
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+pub use plugin::Plugin;
+
+pub mod plugin {
+    use crate::emulator::{cpu::Event, engine::Chip8Core, input::Key};
+
+    /// An extension that follows the emulator as it runs, such as a visualizer, an exporter
+    /// or an agent playing the game. Plugins are compiled in and handed to
+    /// `emulator::run_with_plugins`. Every method has a default that does nothing, so a
+    /// plugin only implements what it needs.
+    pub trait Plugin {
+        fn name(&self) -> &str;
+
+        /// Called for every event the CPU reports, right after the instruction causing it.
+        fn on_event(&mut self, _cpu: &dyn Chip8Core, _event: Event) {}
+
+        /// Called once per frame, at 60Hz, while the program is running. Copy what's needed
+        /// out of it, like the display, to keep a snapshot.
+        fn on_frame(&mut self, _cpu: &dyn Chip8Core) {}
+
+        /// Keys to press (`true`) or release (`false`) before the next frame runs.
+        fn input(&mut self, _cpu: &dyn Chip8Core) -> Vec<(Key, bool)> {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,70 @@
+pub use quirks::Quirks;
+
+pub mod quirks {
+    use serde::{Deserialize, Serialize};
+
+    use crate::emulator::Chip8Result;
+
+    /// Behaviors that differ between CHIP-8 interpreters, which programs may rely on.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+    pub struct Quirks {
+        /// 8XY6 and 8XYE shift VY into VX, rather than shifting VX in place
+        pub shift_vy: bool,
+        /// FX55 and FX65 leave I pointing past the last register they stored or read
+        pub memory_increments_i: bool,
+        /// BNNN jumps to XNN plus VX, rather than NNN plus V0
+        pub jump_vx: bool,
+        /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+        pub vf_reset: bool,
+        /// Sprites are clipped at the edges of the screen, rather than wrapping around
+        pub clip_sprites: bool,
+    }
+
+    impl Quirks {
+        pub const NAMES: [&'static str; 5] = [
+            "shift_vy", "memory_increments_i", "jump_vx", "vf_reset", "clip_sprites"
+        ];
+
+        pub fn description(name: &str) -> &'static str {
+            match name {
+                "shift_vy" => "8XY6/8XYE shift VY into VX instead of shifting VX in place",
+                "memory_increments_i" => "FX55/FX65 advance I past the registers they store or read",
+                "jump_vx" => "BNNN jumps to XNN + VX instead of NNN + V0",
+                "vf_reset" => "8XY1/8XY2/8XY3 reset VF to 0",
+                "clip_sprites" => "Sprites are clipped at the screen edges instead of wrapping",
+                _ => "",
+            }
+        }
+
+        pub fn get(&self, name: &str) -> Chip8Result<bool> {
+            Ok(*self.field(name)?)
+        }
+
+        pub fn set(&mut self, name: &str, value: bool) -> Chip8Result<()> {
+            *self.field_mut(name)? = value;
+            Ok(())
+        }
+
+        fn field(&self, name: &str) -> Chip8Result<&bool> {
+            match name {
+                "shift_vy" => Ok(&self.shift_vy),
+                "memory_increments_i" => Ok(&self.memory_increments_i),
+                "jump_vx" => Ok(&self.jump_vx),
+                "vf_reset" => Ok(&self.vf_reset),
+                "clip_sprites" => Ok(&self.clip_sprites),
+                _ => Err(format!("Unknown quirk: {}", name).into()),
+            }
+        }
+
+        fn field_mut(&mut self, name: &str) -> Chip8Result<&mut bool> {
+            match name {
+                "shift_vy" => Ok(&mut self.shift_vy),
+                "memory_increments_i" => Ok(&mut self.memory_increments_i),
+                "jump_vx" => Ok(&mut self.jump_vx),
+                "vf_reset" => Ok(&mut self.vf_reset),
+                "clip_sprites" => Ok(&mut self.clip_sprites),
+                _ => Err(format!("Unknown quirk: {}", name).into()),
+            }
+        }
+    }
+}
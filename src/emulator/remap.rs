@@ -0,0 +1,72 @@
+pub use remap::RemapMenu;
+pub use remap::RemapAction;
+pub use remap::REMAP_KEY;
+pub use remap::KEYPAD;
+
+pub mod remap {
+    use std::collections::{BTreeMap, HashMap};
+    use sdl2::keyboard::Keycode;
+
+    use crate::emulator::input::Key;
+
+    pub const REMAP_KEY: Keycode = Keycode::F7;
+
+    /// The CHIP-8 keys in the order they're laid out on the keypad, row by row
+    pub const KEYPAD: [Key; 16] = [
+        Key::Num1, Key::Num2, Key::Num3, Key::C,
+        Key::Num4, Key::Num5, Key::Num6, Key::D,
+        Key::Num7, Key::Num8, Key::Num9, Key::E,
+        Key::A, Key::Num0, Key::B, Key::F,
+    ];
+
+    pub enum RemapAction {
+        None,
+        Close,
+        /// Every key has been visited, with the resulting keymap in the form the settings keep it
+        Done(BTreeMap<String, String>),
+    }
+
+    /// The overlay that asks for the host key of each CHIP-8 key in turn.
+    pub struct RemapMenu {
+        /// The position in `KEYPAD` of the key being asked for
+        pub current: usize,
+        /// The host key of every CHIP-8 key so far
+        pub keys: HashMap<Key, Keycode>,
+    }
+
+    impl RemapMenu {
+        pub fn open(keymap: &HashMap<Keycode, Key>) -> RemapMenu {
+            RemapMenu {
+                current: 0,
+                keys: keymap.iter().map(|(code, key)| (*key, *code)).collect(),
+            }
+        }
+
+        /// The name of the host key a CHIP-8 key is mapped to, if any.
+        pub fn host_key(&self, key: Key) -> Option<String> {
+            self.keys.get(&key).map(|code| code.name())
+        }
+
+        /// Maps the key being asked for to `code` and moves to the next one. Backspace
+        /// keeps the current mapping and Escape leaves without changing anything.
+        pub fn handle_key(&mut self, code: Keycode) -> RemapAction {
+            match code {
+                Keycode::Escape => return RemapAction::Close,
+                Keycode::Backspace => {}
+                _ => {
+                    // A host key can only press one CHIP-8 key, so it's taken from the key it pressed before
+                    self.keys.retain(|_, host| *host != code);
+                    self.keys.insert(KEYPAD[self.current], code);
+                }
+            }
+
+            self.current += 1;
+            if self.current < KEYPAD.len() {
+                return RemapAction::None;
+            }
+            RemapAction::Done(self.keys.iter()
+                .map(|(key, code)| (code.name(), format!("{:X}", key.chip8_code())))
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,244 @@
+pub use replay::Replay;
+pub use replay::ReplayRecorder;
+pub use replay::ReplayPlayer;
+pub use replay::EXPORT_REPLAY_KEY;
+
+pub mod replay {
+    use std::{collections::VecDeque, fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+    use sdl2::keyboard::Keycode;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    use crate::emulator::{Chip8Result, cpu::CPU, engine::Chip8Core, input::{Input, InputSource}, quirks::Quirks, state::SaveState, storage};
+
+    pub const EXPORT_REPLAY_KEY: Keycode = Keycode::F6;
+
+    const MAGIC: &[u8; 4] = b"RC8R";
+    const VERSION: u8 = 2;
+    /// Seconds of emulated time in each segment of the recording
+    const SEGMENT_SECONDS: u64 = 5;
+    /// Segments kept, so a replay covers between 25 and 30 seconds
+    const SEGMENTS: usize = 6;
+
+    /// A state, and the keys held and the timer ticks from then on, instruction by instruction.
+    /// This replays the program exactly since random numbers are drawn from the machine's own
+    /// state.
+    #[derive(Serialize, Deserialize)]
+    pub struct Replay<C = CPU> {
+        /// SHA-1 of the program, so a replay isn't played back against another one
+        pub rom_sha1: String,
+        pub state: SaveState<C>,
+        /// The quirks the program ran with, which it may not play back the same way without
+        pub quirks: Quirks,
+        /// Instructions the replay lasts
+        pub length: u64,
+        /// The keys held as a mask of CHIP-8 codes, from the instruction each entry starts at
+        pub inputs: Vec<(u64, u16)>,
+        /// The instructions the timers ticked before, with the number of ticks
+        pub ticks: Vec<(u64, u32)>,
+    }
+
+    impl Replay {
+        /// Replays are named after the ROM's hash and the time they were saved.
+        pub fn path(rom_hash: &str) -> Chip8Result<PathBuf> {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            Ok(storage::data_dir("replays")?.join(format!("{}.{}.replay", rom_hash, timestamp)))
+        }
+
+        /// The most recent replay saved for the ROM with the hash `rom_hash`, if there is one.
+        pub fn latest(rom_hash: &str) -> Chip8Result<Option<PathBuf>> {
+            let prefix = format!("{}.", rom_hash);
+            let mut latest: Option<(u64, PathBuf)> = None;
+            for entry in fs::read_dir(storage::data_dir("replays")?)? {
+                let path = entry?.path();
+                let timestamp = path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix(&prefix))
+                    .and_then(|rest| rest.strip_suffix(".replay"))
+                    .and_then(|timestamp| timestamp.parse::<u64>().ok());
+                let newer = match (timestamp, &latest) {
+                    (Some(timestamp), Some((newest, _))) => timestamp > *newest,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if newer {
+                    latest = timestamp.map(|timestamp| (timestamp, path));
+                }
+            }
+            Ok(latest.map(|(_, path)| path))
+        }
+    }
+
+    impl<C: Chip8Core + Serialize + DeserializeOwned> Replay<C> {
+        /// How long the replay lasts in real time
+        pub fn seconds(&self) -> u64 {
+            self.ticks.iter().map(|(_, ticks)| *ticks as u64).sum::<u64>() / 60
+        }
+
+        pub fn save(&self, path: &Path) -> Chip8Result<()> {
+            let mut out = MAGIC.to_vec();
+            out.push(VERSION);
+            bincode::serialize_into(&mut out, self)?;
+            fs::write(path, out)?;
+            Ok(())
+        }
+
+        pub fn load(path: &str) -> Chip8Result<Replay<C>> {
+            let bytes = fs::read(path)?;
+            if bytes.len() <= MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+                return Err(format!("{} is not a rschip8 replay", path).into());
+            }
+            let version = bytes[MAGIC.len()];
+            if version != VERSION {
+                return Err(format!("Unsupported replay version: {}", version).into());
+            }
+
+            let replay: Replay<C> = bincode::deserialize(&bytes[MAGIC.len() + 1..])?;
+            replay.state.cpu.validate()?;
+            Ok(replay)
+        }
+    }
+
+    /// A stretch of the recording, starting from a state of its own.
+    struct Segment<C> {
+        state: SaveState<C>,
+        instructions: u64,
+        inputs: Vec<(u64, u16)>,
+        keys: u16,
+        ticks: Vec<(u64, u32)>,
+        seconds: f64,
+    }
+
+    /// Keeps the last half minute or so of the program running, so it can be exported as
+    /// a replay after something interesting happened. Only the keys and timer ticks are
+    /// recorded for each instruction, along with a state every few seconds that older input
+    /// is dropped with.
+    pub struct ReplayRecorder<C = CPU> {
+        segments: VecDeque<Segment<C>>,
+    }
+
+    impl<C: Chip8Core + Clone> ReplayRecorder<C> {
+        pub fn new() -> ReplayRecorder<C> {
+            ReplayRecorder::default()
+        }
+
+        /// Records the keys held for the instruction `cpu` is about to execute, and the
+        /// number of times the timers ticked just before it.
+        pub fn before_instruction(&mut self, cpu: &C, timer_ticks: u32) {
+            let segment_full = match self.segments.back() {
+                Some(segment) => segment.seconds >= SEGMENT_SECONDS as f64,
+                None => true,
+            };
+            if segment_full {
+                if self.segments.len() == SEGMENTS {
+                    self.segments.pop_front();
+                }
+                self.segments.push_back(Segment {
+                    state: SaveState::capture(cpu),
+                    instructions: 0,
+                    inputs: vec![(0, cpu.held_keys())],
+                    keys: cpu.held_keys(),
+                    ticks: Vec::new(),
+                    seconds: 0.0,
+                });
+            }
+
+            let segment = self.segments.back_mut().unwrap();
+            let keys = cpu.held_keys();
+            if keys != segment.keys {
+                segment.inputs.push((segment.instructions, keys));
+                segment.keys = keys;
+            }
+            if timer_ticks > 0 {
+                segment.ticks.push((segment.instructions, timer_ticks));
+                segment.seconds += timer_ticks as f64 / 60.0;
+            }
+            segment.instructions += 1;
+        }
+
+        /// Drops what was recorded, for when the machine's state is replaced by a reset or a
+        /// loaded state, which the recording can't follow.
+        pub fn restart(&mut self) {
+            self.segments.clear();
+        }
+
+        pub fn export(&self, rom_sha1: &str) -> Option<Replay<C>> {
+            let first = self.segments.front()?;
+            let mut inputs = Vec::new();
+            let mut ticks = Vec::new();
+            let mut length = 0;
+            for segment in self.segments.iter() {
+                inputs.extend(segment.inputs.iter().map(|(start, keys)| (length + start, *keys)));
+                ticks.extend(segment.ticks.iter().map(|(at, count)| (length + at, *count)));
+                length += segment.instructions;
+            }
+
+            Some(Replay {
+                rom_sha1: rom_sha1.to_string(),
+                state: SaveState { cpu: first.state.cpu.clone(), timestamp: first.state.timestamp },
+                quirks: first.state.cpu.quirks(),
+                length,
+                inputs,
+                ticks,
+            })
+        }
+    }
+
+    impl<C> Default for ReplayRecorder<C> {
+        fn default() -> ReplayRecorder<C> {
+            ReplayRecorder { segments: VecDeque::new() }
+        }
+    }
+
+    /// Holds the keys of a replay down and ticks its timers, instruction by instruction.
+    pub struct ReplayPlayer {
+        inputs: Vec<(u64, u16)>,
+        next: usize,
+        ticks: Vec<(u64, u32)>,
+        next_tick: usize,
+        position: u64,
+        length: u64,
+        keys: u16,
+    }
+
+    impl ReplayPlayer {
+        /// Restores the replay's state and quirks into `cpu` and starts playing it back, taking
+        /// over `input` until the replay is stopped with `Input::hand_back`.
+        pub fn start<C: Chip8Core + Clone>(replay: Replay<C>, cpu: &mut C, input: &mut Input) -> ReplayPlayer {
+            replay.state.restore(cpu);
+            if cpu.quirks() != replay.quirks {
+                eprintln!("Using the quirks the replay was recorded with");
+                cpu.set_quirks(replay.quirks);
+            }
+            input.take_over(InputSource::Replay);
+            ReplayPlayer {
+                inputs: replay.inputs,
+                next: 0,
+                ticks: replay.ticks,
+                next_tick: 0,
+                position: 0,
+                length: replay.length,
+                keys: 0,
+            }
+        }
+
+        /// Sets the keys for the next instruction, and returns the number of times the timers
+        /// tick before it. Returns `None` once the replay is over.
+        pub fn before_instruction(&mut self, input: &mut Input) -> Option<u32> {
+            if self.position >= self.length {
+                return None;
+            }
+            while self.next < self.inputs.len() && self.inputs[self.next].0 == self.position {
+                self.keys = self.inputs[self.next].1;
+                self.next += 1;
+            }
+            input.set_keys(InputSource::Replay, self.keys);
+            let mut timer_ticks = 0;
+            if self.next_tick < self.ticks.len() && self.ticks[self.next_tick].0 == self.position {
+                timer_ticks = self.ticks[self.next_tick].1;
+                self.next_tick += 1;
+            }
+            self.position += 1;
+            Some(timer_ticks)
+        }
+    }
+}
@@ -0,0 +1,108 @@
+pub use rom::RomHashes;
+pub use rom::RomFormat;
+pub use rom::Rom;
+pub use rom::load;
+
+pub mod rom {
+    use std::{fmt, fs};
+
+    use crate::emulator::{Chip8Result, bundle, settings::Settings};
+
+    /// Hashes identifying a ROM regardless of its file name. The SHA-1 keys everything
+    /// rschip8 stores for a ROM, the CRC32 is a shorter form that's easier to compare by eye.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub struct RomHashes {
+        pub sha1: String,
+        pub crc32: u32,
+    }
+
+    impl RomHashes {
+        pub fn of(bytes: &[u8]) -> RomHashes {
+            RomHashes {
+                sha1: sha1_smol::Sha1::from(bytes).digest().to_string(),
+                crc32: crc32fast::hash(bytes),
+            }
+        }
+    }
+
+    impl fmt::Display for RomHashes {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "SHA-1 {}, CRC32 {:08x}", self.sha1, self.crc32)
+        }
+    }
+
+    /// How a ROM file is encoded.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum RomFormat {
+        Binary,
+        /// Bytes written as hex text, separated by whitespace or commas and optionally prefixed
+        /// with `0x`, the way Octo exports programs and old listings print them
+        Hex,
+        /// A rschip8 bundle, holding the program along with its title, author and settings.
+        /// It's never detected, only picked with `--format bundle`.
+        Bundle,
+    }
+
+    /// A program, along with what the file it was loaded from says about it.
+    pub struct Rom {
+        pub program: Vec<u8>,
+        pub title: Option<String>,
+        pub author: Option<String>,
+        /// Settings the program should be run with unless they're overridden
+        pub settings: Settings,
+    }
+
+    impl Rom {
+        pub fn new(program: Vec<u8>) -> Rom {
+            Rom { program, title: None, author: None, settings: Settings::default() }
+        }
+    }
+
+    impl RomFormat {
+        pub fn from_name(name: &str) -> Chip8Result<RomFormat> {
+            match name {
+                "binary" => Ok(RomFormat::Binary),
+                "hex" => Ok(RomFormat::Hex),
+                "bundle" => Ok(RomFormat::Bundle),
+                _ => Err(format!("Unknown ROM format: {}", name).into()),
+            }
+        }
+
+        /// Guesses the format from the contents: a file that parses as hex text is taken
+        /// to be one, as a binary program is very unlikely to.
+        pub fn detect(bytes: &[u8]) -> RomFormat {
+            match parse_hex(bytes) {
+                Ok(program) if !program.is_empty() => RomFormat::Hex,
+                _ => RomFormat::Binary,
+            }
+        }
+    }
+
+    /// Reads a ROM, decoding it from `format`, or from the format it appears to be in if none is given.
+    pub fn load(path: &str, format: Option<RomFormat>) -> Chip8Result<Rom> {
+        let bytes = fs::read(path)?;
+        match format.unwrap_or_else(|| RomFormat::detect(&bytes)) {
+            RomFormat::Binary => Ok(Rom::new(bytes)),
+            RomFormat::Hex => parse_hex(&bytes)
+                .map(Rom::new)
+                .map_err(|e| format!("{} is not a valid hex ROM: {}", path, e).into()),
+            RomFormat::Bundle => bundle::parse(&bytes)
+                .map_err(|e| format!("{} is not a valid bundle: {}", path, e).into()),
+        }
+    }
+
+    fn parse_hex(text: &[u8]) -> Chip8Result<Vec<u8>> {
+        let text = std::str::from_utf8(text)?;
+        let mut program = Vec::new();
+        for token in text.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+            let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+            if digits.is_empty() || digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid byte: {}", token).into());
+            }
+            for i in (0..digits.len()).step_by(2) {
+                program.push(u8::from_str_radix(&digits[i..i + 2], 16)?);
+            }
+        }
+        Ok(program)
+    }
+}
@@ -0,0 +1,187 @@
+pub use romdb::RomDatabase;
+pub use romdb::KnownRom;
+
+pub mod romdb {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use crate::emulator::{Chip8Error, Chip8Result, rom::RomHashes, storage};
+
+    const BUNDLED: &str = include_str!("known_roms.txt");
+
+    pub struct KnownRom {
+        pub sha1: String,
+        pub good_dump: bool,
+        pub platform: String,
+        pub title: String,
+    }
+
+    /// Known dumps, bundled and local, along with the ROMs that have been loaded before.
+    pub struct RomDatabase {
+        known: HashMap<String, KnownRom>,
+        seen: HashMap<String, String>,
+    }
+
+    impl RomDatabase {
+        pub fn load() -> Chip8Result<RomDatabase> {
+            let mut db = RomDatabase { known: HashMap::new(), seen: HashMap::new() };
+            db.add_known(parse_known(BUNDLED, "the bundled ROM database")?);
+
+            // A mistake in the local entries shouldn't keep any ROM from running
+            let local = storage::data_dir("")?.join("known_roms.txt");
+            if local.exists() {
+                let source = local.display().to_string();
+                let known = fs::read_to_string(&local)
+                    .map_err(|e| Chip8Error::from(format!("Could not read {}: {}", source, e)))
+                    .and_then(|contents| parse_known(&contents, &source));
+                match known {
+                    Ok(known) => db.add_known(known),
+                    Err(e) => eprintln!("Warning: {}, only the bundled ROM database is used", e),
+                }
+            }
+
+            let seen = storage::data_dir("")?.join("seen_roms.txt");
+            if seen.exists() {
+                for line in fs::read_to_string(&seen)?.lines() {
+                    if let Some((sha1, name)) = line.split_once(' ') {
+                        db.seen.insert(sha1.to_string(), name.to_string());
+                    }
+                }
+            }
+
+            Ok(db)
+        }
+
+        fn add_known(&mut self, known: Vec<KnownRom>) {
+            for rom in known {
+                self.known.insert(rom.sha1.clone(), rom);
+            }
+        }
+
+        pub fn lookup(&self, hashes: &RomHashes) -> Option<&KnownRom> {
+            self.known.get(&hashes.sha1)
+        }
+
+        /// Returns warnings about problems with the ROM at `program` that would otherwise
+        /// look like emulation bugs.
+        pub fn verify(&self, hashes: &RomHashes, program: &str) -> Vec<String> {
+            let mut warnings = Vec::new();
+            let name = file_name(program);
+
+            if let Some(known) = self.lookup(hashes) {
+                if !known.good_dump {
+                    warnings.push(format!("{} is a known bad dump of {}", name, known.title));
+                }
+                match known.platform.as_str() {
+                    "chip8" => {}
+                    "schip" => warnings.push(format!("{} is a SUPER-CHIP program", known.title)),
+                    "xochip" => warnings.push(format!("{} is an XO-CHIP program", known.title)),
+                    platform => warnings.push(format!("{} targets {}", known.title, platform)),
+                }
+            }
+
+            if let Some(seen_as) = self.seen.get(&hashes.sha1) {
+                if *seen_as != name {
+                    warnings.push(format!("{} is the same ROM as {}, loaded before", name, seen_as));
+                }
+            }
+
+            warnings
+        }
+
+        /// Remembers the name the ROM was loaded under, so copies under other names can be spotted.
+        pub fn remember(&mut self, hashes: &RomHashes, program: &str) -> Chip8Result<()> {
+            if self.seen.contains_key(&hashes.sha1) {
+                return Ok(());
+            }
+            self.seen.insert(hashes.sha1.clone(), file_name(program));
+
+            let mut contents = String::new();
+            for (sha1, name) in self.seen.iter() {
+                contents.push_str(&format!("{} {}\n", sha1, name));
+            }
+            fs::write(storage::data_dir("")?.join("seen_roms.txt"), contents)?;
+            Ok(())
+        }
+    }
+
+    /// Reads entries in the `<sha1> <status> <platform> <title>` format, naming `source` in errors.
+    fn parse_known(contents: &str, source: &str) -> Chip8Result<Vec<KnownRom>> {
+        let mut known = Vec::new();
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.splitn(4, char::is_whitespace).collect();
+            let invalid = || format!("Invalid entry on line {} of {}", n + 1, source);
+            if fields.len() != 4 {
+                return Err(invalid().into());
+            }
+            let good_dump = match fields[1] {
+                "good" => true,
+                "bad" => false,
+                _ => return Err(invalid().into()),
+            };
+
+            known.push(KnownRom {
+                sha1: fields[0].to_lowercase(),
+                good_dump,
+                platform: fields[2].to_string(),
+                title: fields[3].trim().to_string(),
+            });
+        }
+        Ok(known)
+    }
+
+    fn file_name(program: &str) -> String {
+        Path::new(program)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| program.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn database(contents: &str) -> RomDatabase {
+            let mut db = RomDatabase { known: HashMap::new(), seen: HashMap::new() };
+            db.add_known(parse_known(contents, "the test database").unwrap());
+            db
+        }
+
+        #[test]
+        fn bundled_entries_are_valid() {
+            let db = database(BUNDLED);
+            for known in db.known.values() {
+                assert_eq!(known.sha1.len(), 40, "{}", known.title);
+                assert!(known.sha1.chars().all(|c| c.is_ascii_hexdigit()), "{}", known.title);
+                assert!(["chip8", "schip", "xochip"].contains(&known.platform.as_str()), "{}", known.title);
+            }
+        }
+
+        #[test]
+        fn verify_warns_about_bad_dumps_and_variants() {
+            let good = RomHashes::of(b"good");
+            let bad = RomHashes::of(b"bad");
+            let variant = RomHashes::of(b"variant");
+            let db = database(&format!(
+                "# A comment\n{} good chip8 Good Game\n\n{} bad chip8 Broken Game\n{} good schip Super Game\n",
+                good.sha1, bad.sha1.to_uppercase(), variant.sha1,
+            ));
+
+            assert_eq!(db.lookup(&good).unwrap().title, "Good Game");
+            assert!(db.verify(&good, "good.ch8").is_empty());
+            assert_eq!(db.verify(&bad, "roms/bad.ch8"), vec!["bad.ch8 is a known bad dump of Broken Game"]);
+            assert_eq!(db.verify(&variant, "super.ch8"), vec!["Super Game is a SUPER-CHIP program"]);
+            assert!(db.lookup(&RomHashes::of(b"unknown")).is_none());
+        }
+
+        #[test]
+        fn rejects_invalid_entries() {
+            assert!(parse_known("0123 fine chip8 Game", "the test database").is_err());
+            assert!(parse_known("0123 good", "the test database").is_err());
+        }
+    }
+}
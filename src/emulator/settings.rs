@@ -0,0 +1,249 @@
+pub use settings::Settings;
+pub use settings::Palette;
+pub use settings::parse_color;
+pub use settings::parse_keycode;
+pub use settings::parse_key;
+pub use settings::parse_display_size;
+pub use settings::parse_macro;
+
+pub mod settings {
+    use std::{collections::{BTreeMap, HashMap}, fs, path::PathBuf};
+    use sdl2::keyboard::Keycode;
+    use serde::{Deserialize, Serialize};
+
+    use crate::emulator::{Chip8Result, input::{self, Key, Macro, MacroStep}, quirks::Quirks, storage, ui, variants::Platform};
+
+    const DEFAULT_SPEED: u32 = 600;
+    const DEFAULT_TURBO_FACTOR: u32 = 8;
+    const DEFAULT_SLOWMO_FACTOR: f64 = 0.25;
+    const DEFAULT_FOREGROUND: (u8, u8, u8) = (255, 255, 255);
+    const DEFAULT_BACKGROUND: (u8, u8, u8) = (0, 0, 0);
+
+    #[derive(Clone, Copy)]
+    pub struct Palette {
+        pub foreground: (u8, u8, u8),
+        pub background: (u8, u8, u8),
+        /// Swaps the foreground and background
+        pub inverted: bool,
+        /// Draws dark pixels on pale green with some ghosting instead, like an old LCD
+        pub lcd: bool,
+    }
+
+    /// Settings that can differ from one ROM to another. Every field is optional so
+    /// that the ones given on the command line can be layered over the ones remembered
+    /// for the ROM, which are in turn layered over the defaults.
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    pub struct Settings {
+        pub speed: Option<u32>,
+        pub turbo_factor: Option<u32>,
+        pub slowmo_factor: Option<f64>,
+        /// Frames emulated ahead of what is shown, to hide input latency
+        pub run_ahead: Option<u32>,
+        pub foreground: Option<String>,
+        pub background: Option<String>,
+        pub invert: Option<bool>,
+        pub lcd: Option<bool>,
+        /// The display's size, e.g. `64x48`
+        pub display: Option<String>,
+        pub platform: Option<String>,
+        /// Quirks turned on or off regardless of the platform
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub quirks: BTreeMap<String, bool>,
+        /// Host key names mapped to the CHIP-8 key (a hex digit) they press.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub keymap: BTreeMap<String, String>,
+        /// Host key names mapped to a macro, e.g. `5:2,_:10,6:2`
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub macros: BTreeMap<String, String>,
+        /// Host key names mapped to how many times per second they repeat while held
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub autofire: BTreeMap<String, u32>,
+    }
+
+    impl Settings {
+        fn path(rom_hash: &str) -> Chip8Result<PathBuf> {
+            Ok(storage::data_dir("roms")?.join(format!("{}.toml", rom_hash)))
+        }
+
+        /// Loads the settings remembered for a ROM, or empty settings if there are none.
+        pub fn load(rom_hash: &str) -> Chip8Result<Settings> {
+            let path = Settings::path(rom_hash)?;
+            if !path.exists() {
+                return Ok(Settings::default());
+            }
+            toml::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("Invalid settings in {}: {}", path.display(), e).into())
+        }
+
+        pub fn save(&self, rom_hash: &str) -> Chip8Result<()> {
+            fs::write(Settings::path(rom_hash)?, toml::to_string(self)?)?;
+            Ok(())
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.speed.is_none()
+                && self.turbo_factor.is_none()
+                && self.slowmo_factor.is_none()
+                && self.run_ahead.is_none()
+                && self.foreground.is_none()
+                && self.background.is_none()
+                && self.invert.is_none()
+                && self.lcd.is_none()
+                && self.display.is_none()
+                && self.platform.is_none()
+                && self.quirks.is_empty()
+                && self.keymap.is_empty()
+                && self.macros.is_empty()
+                && self.autofire.is_empty()
+        }
+
+        /// Returns these settings with every setting present in `overrides` replaced.
+        pub fn merge(mut self, overrides: Settings) -> Settings {
+            self.keymap.retain(|_, key| !overrides.keymap.values().any(|k| k == key));
+            self.keymap.extend(overrides.keymap);
+            self.speed = overrides.speed.or(self.speed);
+            self.turbo_factor = overrides.turbo_factor.or(self.turbo_factor);
+            self.slowmo_factor = overrides.slowmo_factor.or(self.slowmo_factor);
+            self.run_ahead = overrides.run_ahead.or(self.run_ahead);
+            self.foreground = overrides.foreground.or(self.foreground);
+            self.background = overrides.background.or(self.background);
+            self.invert = overrides.invert.or(self.invert);
+            self.lcd = overrides.lcd.or(self.lcd);
+            self.display = overrides.display.or(self.display);
+            self.platform = overrides.platform.or(self.platform);
+            self.quirks.extend(overrides.quirks);
+            self.macros.extend(overrides.macros);
+            self.autofire.extend(overrides.autofire);
+            self
+        }
+
+        /// Instructions executed per second
+        pub fn speed(&self) -> u32 {
+            self.speed.unwrap_or(DEFAULT_SPEED)
+        }
+
+        pub fn turbo_factor(&self) -> u32 {
+            self.turbo_factor.unwrap_or(DEFAULT_TURBO_FACTOR)
+        }
+
+        pub fn slowmo_factor(&self) -> f64 {
+            self.slowmo_factor.unwrap_or(DEFAULT_SLOWMO_FACTOR)
+        }
+
+        pub fn run_ahead(&self) -> u32 {
+            self.run_ahead.unwrap_or(0)
+        }
+
+        pub fn palette(&self) -> Chip8Result<Palette> {
+            Ok(Palette {
+                foreground: match &self.foreground {
+                    Some(color) => parse_color(color)?,
+                    None => DEFAULT_FOREGROUND,
+                },
+                background: match &self.background {
+                    Some(color) => parse_color(color)?,
+                    None => DEFAULT_BACKGROUND,
+                },
+                inverted: self.invert.unwrap_or(false),
+                lcd: self.lcd.unwrap_or(false),
+            })
+        }
+
+        /// The number of rows of the display
+        pub fn display_height(&self) -> Chip8Result<usize> {
+            match &self.display {
+                Some(size) => parse_display_size(size),
+                None => Ok(ui::HEIGHT as usize),
+            }
+        }
+
+        /// Returns the platform that was chosen, if any
+        pub fn platform(&self) -> Chip8Result<Option<Platform>> {
+            self.platform.as_deref().map(Platform::from_name).transpose()
+        }
+
+        pub fn apply_quirks(&self, quirks: &mut Quirks) -> Chip8Result<()> {
+            for (name, value) in self.quirks.iter() {
+                quirks.set(name, *value)?;
+            }
+            Ok(())
+        }
+
+        /// Returns the default keymap with the remapped CHIP-8 keys moved to their new host keys.
+        pub fn keymap(&self) -> Chip8Result<HashMap<Keycode, Key>> {
+            let mut overrides = HashMap::new();
+            for (host, chip8) in self.keymap.iter() {
+                overrides.insert(parse_keycode(host)?, parse_key(chip8)?);
+            }
+
+            let mut keymap: HashMap<Keycode, Key> = input::DEFAULT_KEYMAP.iter()
+                .filter(|(_, key)| !overrides.values().any(|k| k == key))
+                .cloned()
+                .collect();
+            keymap.extend(overrides);
+            Ok(keymap)
+        }
+
+        pub fn macros(&self) -> Chip8Result<HashMap<Keycode, Macro>> {
+            self.macros.iter()
+                .map(|(host, steps)| Ok((parse_keycode(host)?, parse_macro(steps)?)))
+                .collect()
+        }
+
+        pub fn autofire(&self) -> Chip8Result<HashMap<Keycode, u32>> {
+            self.autofire.iter()
+                .map(|(host, rate)| Ok((parse_keycode(host)?, *rate)))
+                .collect()
+        }
+    }
+
+    /// Parses a color given as `#rrggbb`
+    pub fn parse_color(color: &str) -> Chip8Result<(u8, u8, u8)> {
+        let invalid = || format!("Invalid color: {}, expected #rrggbb", color);
+        let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+        if hex.len() != 6 {
+            return Err(invalid().into());
+        }
+        let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        Ok(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+
+    pub fn parse_keycode(name: &str) -> Chip8Result<Keycode> {
+        Keycode::from_name(name).ok_or_else(|| format!("Unknown key: {}", name).into())
+    }
+
+    /// Parses a CHIP-8 key given as a single hex digit
+    pub fn parse_key(digit: &str) -> Chip8Result<Key> {
+        u8::from_str_radix(digit, 16).ok()
+            .filter(|_| digit.len() == 1)
+            .and_then(Key::from_chip8_code)
+            .ok_or_else(|| format!("Invalid CHIP-8 key: {}, expected 0-F", digit).into())
+    }
+
+    /// Parses a macro given as comma separated `<0-F>:<frames>` steps, each of which
+    /// holds a key down for that many frames. `_` in place of a key just waits.
+    pub fn parse_macro(steps: &str) -> Chip8Result<Macro> {
+        let invalid = || format!("Invalid macro: {}, expected steps like 5:2,_:10,6:2", steps);
+        let steps = steps.split(',')
+            .map(|step| {
+                let (key, frames) = step.trim().split_once(':').ok_or_else(invalid)?;
+                let key = match key {
+                    "_" => None,
+                    key => Some(parse_key(key)?),
+                };
+                let frames = frames.parse::<u32>().ok().filter(|&f| f > 0).ok_or_else(invalid)?;
+                Ok(MacroStep { key, frames })
+            })
+            .collect::<Chip8Result<Vec<MacroStep>>>()?;
+        Ok(Macro { steps })
+    }
+
+    /// Parses a display size like `64x48`, returning its height.
+    pub fn parse_display_size(size: &str) -> Chip8Result<usize> {
+        let sizes: Vec<String> = ui::HEIGHTS.iter().map(|height| format!("{}x{}", ui::WIDTH, height)).collect();
+        match sizes.iter().position(|s| s == size) {
+            Some(i) => Ok(ui::HEIGHTS[i]),
+            None => Err(format!("Unsupported display size: {}, expected one of {}", size, sizes.join(", ")).into()),
+        }
+    }
+}
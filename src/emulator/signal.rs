@@ -0,0 +1,49 @@
+pub use signal::install_dump_handler;
+pub use signal::dump_if_requested;
+
+pub mod signal {
+    use std::{path::PathBuf, process, sync::atomic::{AtomicBool, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+
+    use crate::emulator::{Chip8Result, dump::{self, Trace}, engine::Chip8Core, storage};
+
+    static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Makes SIGUSR1 ask for a dump of the machine, to look into a program that seems stuck
+    /// without stopping it. Signals only exist on Unix, elsewhere this does nothing.
+    pub fn install_dump_handler() -> Chip8Result<()> {
+        #[cfg(unix)]
+        {
+            let handler = request_dump as extern "C" fn(libc::c_int);
+            // The handler only sets a flag, which is all a signal handler can safely do
+            if unsafe { libc::signal(libc::SIGUSR1, handler as libc::sighandler_t) } == libc::SIG_ERR {
+                return Err("Could not install a handler for SIGUSR1".into());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    extern "C" fn request_dump(_signal: libc::c_int) {
+        DUMP_REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes a dump to `~/.rschip8/dumps` if one was asked for since the last call. A dump
+    /// that can't be written is only reported, so the program keeps running either way.
+    pub fn dump_if_requested(cpu: &dyn Chip8Core, trace: &Trace) {
+        if !DUMP_REQUESTED.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        match write(cpu, trace) {
+            Ok(path) => eprintln!("Wrote a dump to {}", path.display()),
+            Err(e) => eprintln!("Could not write a dump: {}", e),
+        }
+    }
+
+    /// Dumps are named after the process they came from, which is what the signal was sent to.
+    fn write(cpu: &dyn Chip8Core, trace: &Trace) -> Chip8Result<PathBuf> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = storage::data_dir("dumps")?.join(format!("rschip8.{}.{}.dump", process::id(), timestamp));
+        dump::write_dump(cpu, None, Some(trace), &path.to_string_lossy())?;
+        Ok(path)
+    }
+}
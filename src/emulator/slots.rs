@@ -0,0 +1,79 @@
+pub use slots::SlotMenu;
+pub use slots::SlotMenuAction;
+pub use slots::SlotMenuMode;
+pub use slots::COLUMNS;
+pub use slots::SAVE_MENU_KEY;
+pub use slots::LOAD_MENU_KEY;
+
+pub mod slots {
+    use sdl2::keyboard::Keycode;
+
+    use crate::emulator::{Chip8Result, state::{SaveState, SLOTS}};
+
+    pub const SAVE_MENU_KEY: Keycode = Keycode::F5;
+    pub const LOAD_MENU_KEY: Keycode = Keycode::F8;
+    pub const COLUMNS: usize = 4;
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum SlotMenuMode {
+        Save,
+        Load,
+    }
+
+    pub enum SlotMenuAction {
+        None,
+        Close,
+        Save(usize),
+        Load(usize),
+    }
+
+    /// The overlay shown while picking a save state slot.
+    pub struct SlotMenu {
+        pub mode: SlotMenuMode,
+        pub selected: usize,
+        pub slots: Vec<Option<SaveState>>,
+    }
+
+    impl SlotMenu {
+        pub fn open(mode: SlotMenuMode, rom_hash: &str) -> Chip8Result<SlotMenu> {
+            let mut slots = Vec::with_capacity(SLOTS);
+            for slot in 0..SLOTS {
+                let path = SaveState::slot_path(rom_hash, slot)?;
+                // A broken slot shouldn't keep the others from being used, so it's shown as empty
+                let state = SaveState::load(&path).unwrap_or_else(|e| {
+                    eprintln!("Ignoring save state {}: {}", path.display(), e);
+                    None
+                });
+                slots.push(state);
+            }
+
+            Ok(SlotMenu { mode, selected: 0, slots })
+        }
+
+        pub fn handle_key(&mut self, code: Keycode) -> SlotMenuAction {
+            match code {
+                Keycode::Escape => SlotMenuAction::Close,
+                Keycode::Left => {
+                    self.selected = (self.selected + SLOTS - 1) % SLOTS;
+                    SlotMenuAction::None
+                }
+                Keycode::Right => {
+                    self.selected = (self.selected + 1) % SLOTS;
+                    SlotMenuAction::None
+                }
+                Keycode::Up | Keycode::Down => {
+                    self.selected = (self.selected + COLUMNS) % SLOTS;
+                    SlotMenuAction::None
+                }
+                Keycode::Return | Keycode::KpEnter => match self.mode {
+                    SlotMenuMode::Save => SlotMenuAction::Save(self.selected),
+                    SlotMenuMode::Load if self.slots[self.selected].is_some() => {
+                        SlotMenuAction::Load(self.selected)
+                    }
+                    SlotMenuMode::Load => SlotMenuAction::None,
+                },
+                _ => SlotMenuAction::None,
+            }
+        }
+    }
+}
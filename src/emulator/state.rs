@@ -0,0 +1,114 @@
+pub use state::SaveState;
+pub use state::SLOTS;
+
+pub mod state {
+    use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    use crate::emulator::{Chip8Result, cpu::CPU, engine::Chip8Core, storage, ui};
+
+    const MAGIC: &[u8; 4] = b"RC8S";
+    const VERSION: u8 = 5;
+    pub const SLOTS: usize = 8;
+
+    /// A snapshot of the machine run by a `Chip8Core`. It includes the frame that was on
+    /// screen when it was taken, so it can be previewed without running the program.
+    #[derive(Serialize, Deserialize)]
+    pub struct SaveState<C = CPU> {
+        pub cpu: C,
+        pub timestamp: u64,
+    }
+
+    impl SaveState {
+        /// States are keyed by the ROM's hash so they can never be loaded into another program.
+        pub fn slot_path(rom_hash: &str, slot: usize) -> Chip8Result<PathBuf> {
+            Ok(storage::data_dir("states")?.join(format!("{}.{}.state", rom_hash, slot)))
+        }
+
+        pub fn auto_save_path(rom_hash: &str) -> Chip8Result<PathBuf> {
+            Ok(storage::data_dir("states")?.join(format!("{}.auto.state", rom_hash)))
+        }
+    }
+
+    impl<C: Chip8Core + Clone> SaveState<C> {
+        pub fn capture(cpu: &C) -> SaveState<C> {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            SaveState {
+                cpu: cpu.clone(),
+                timestamp,
+            }
+        }
+
+        /// Restores the machine, keeping the quirks and hooks currently in use since they're
+        /// part of the configuration rather than the machine's state.
+        pub fn restore(&self, cpu: &mut C) {
+            cpu.load_machine_state(&self.cpu);
+        }
+
+        pub fn buffer(&self) -> &ui::Buffer {
+            self.cpu.display()
+        }
+    }
+
+    impl<C: Chip8Core + Serialize + DeserializeOwned> SaveState<C> {
+        pub fn save(&self, path: &Path) -> Chip8Result<()> {
+            fs::write(path, self.to_bytes()?)?;
+            Ok(())
+        }
+
+        /// Loads the state stored at `path`, or returns `None` if there is no such file.
+        pub fn load(path: &Path) -> Chip8Result<Option<SaveState<C>>> {
+            if !path.exists() {
+                return Ok(None);
+            }
+            Ok(Some(SaveState::from_bytes(&fs::read(path)?)?))
+        }
+
+        /// Encodes the state with bincode, after a header identifying the file and its version.
+        pub fn to_bytes(&self) -> Chip8Result<Vec<u8>> {
+            let mut out = MAGIC.to_vec();
+            out.push(VERSION);
+            bincode::serialize_into(&mut out, self)?;
+            Ok(out)
+        }
+
+        pub fn from_bytes(bytes: &[u8]) -> Chip8Result<SaveState<C>> {
+            if bytes.len() <= MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+                return Err("Not a rschip8 save state".into());
+            }
+            let version = bytes[MAGIC.len()];
+            if version != VERSION {
+                return Err(format!("Unsupported save state version: {}", version).into());
+            }
+
+            let state: SaveState<C> = bincode::deserialize(&bytes[MAGIC.len() + 1..])?;
+            state.cpu.validate()?;
+            Ok(state)
+        }
+
+        /// Formats the timestamp as a UTC date and time, e.g. `2022-03-14 15:09`.
+        pub fn formatted_timestamp(&self) -> String {
+            let days = (self.timestamp / 86400) as i64;
+            let seconds = self.timestamp % 86400;
+
+            // Converts days since the epoch into a civil date, see
+            // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+            let z = days + 719468;
+            let era = z.div_euclid(146097);
+            let doe = z.rem_euclid(146097);
+            let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+            let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+            let mp = (5 * doy + 2) / 153;
+            let day = doy - (153 * mp + 2) / 5 + 1;
+            let month = if mp < 10 { mp + 3 } else { mp - 9 };
+            let year = yoe + era * 400 + (month <= 2) as i64;
+
+            format!("{:04}-{:02}-{:02} {:02}:{:02}",
+                year, month, day, seconds / 3600, seconds % 3600 / 60)
+        }
+    }
+}
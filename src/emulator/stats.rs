@@ -0,0 +1,93 @@
+pub use stats::report;
+
+pub mod stats {
+    use std::collections::BTreeMap;
+
+    use crate::emulator::{Chip8Result, analysis, instruction::{self, Instruction}, rom::{self, RomFormat}, variants::{Detection, ForeignOpcode, Platform}};
+
+    /// Prints what the reachable code of a program is made of: how often each kind of
+    /// instruction appears, how much it draws, calls and uses random numbers, the platform
+    /// it seems to be written for and how deep its subroutine calls nest.
+    pub fn report(program: &str, format: Option<RomFormat>) -> Chip8Result<()> {
+        let rom = rom::load(program, format)?.program;
+        let code = analysis::reachable_instructions(&rom);
+
+        let mut histogram: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for (_, opcode) in code.iter() {
+            *histogram.entry(pattern(*opcode)).or_default() += 1;
+        }
+        let count = |patterns: &[&str]| patterns.iter().map(|p| histogram.get(p).cloned().unwrap_or(0)).sum::<usize>();
+
+        println!("{}: {} bytes, {} reachable instructions", program, rom.len(), code.len());
+        println!();
+        println!("Draws:  {}", count(&["DXYN", "00E0"]));
+        println!("Calls:  {}", count(&["2NNN"]));
+        println!("Random: {}", count(&["CXNN"]));
+        println!("Input:  {}", count(&["EX9E", "EXA1", "FX0A"]));
+        println!("Sound:  {}", count(&["FX18"]));
+        println!();
+
+        let platform = match Detection::scan(&rom) {
+            Some(detection) => detection.platform,
+            None => Platform::Chip8,
+        };
+        println!("Platform:    {}", platform);
+        match analysis::stack_depth(&rom) {
+            Some(depth) => println!("Stack depth: {}", depth),
+            None => println!("Stack depth: unknown, subroutines are recursive"),
+        }
+        println!();
+
+        let mut rows: Vec<(&str, usize)> = histogram.into_iter().collect();
+        rows.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        for (pattern, count) in rows {
+            println!("{:<8} {:>5}  {:>5.1}%", pattern, count, 100.0 * count as f64 / code.len() as f64);
+        }
+        Ok(())
+    }
+
+    /// The pattern an opcode matches, as CHIP-8 instructions are usually written.
+    fn pattern(opcode: u16) -> &'static str {
+        match instruction::decode(opcode) {
+            Instruction::ClearScreen => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::Sys(_) => "0NNN",
+            Instruction::Jump(_) => "1NNN",
+            Instruction::Call(_) => "2NNN",
+            Instruction::SkipEqImm(..) => "3XNN",
+            Instruction::SkipNeqImm(..) => "4XNN",
+            Instruction::SkipEqReg(..) => "5XY0",
+            Instruction::LoadImm(..) => "6XNN",
+            Instruction::AddImm(..) => "7XNN",
+            Instruction::LoadReg(..) => "8XY0",
+            Instruction::Or(..) => "8XY1",
+            Instruction::And(..) => "8XY2",
+            Instruction::Xor(..) => "8XY3",
+            Instruction::AddReg(..) => "8XY4",
+            Instruction::Sub(..) => "8XY5",
+            Instruction::Shr(..) => "8XY6",
+            Instruction::SubN(..) => "8XY7",
+            Instruction::Shl(..) => "8XYE",
+            Instruction::SkipNeqReg(..) => "9XY0",
+            Instruction::SetI(_) => "ANNN",
+            Instruction::JumpOffset(_) => "BNNN",
+            Instruction::Random(..) => "CXNN",
+            Instruction::Draw(..) => "DXYN",
+            Instruction::SkipKeyDown(_) => "EX9E",
+            Instruction::SkipKeyUp(_) => "EXA1",
+            Instruction::LoadDelay(_) => "FX07",
+            Instruction::WaitKey(_) => "FX0A",
+            Instruction::SetDelay(_) => "FX15",
+            Instruction::SetSound(_) => "FX18",
+            Instruction::AddI(_) => "FX1E",
+            Instruction::LoadFont(_) => "FX29",
+            Instruction::StoreBcd(_) => "FX33",
+            Instruction::StoreRegisters(_) => "FX55",
+            Instruction::ReadRegisters(_) => "FX65",
+            Instruction::Invalid(_) => match ForeignOpcode::identify(opcode) {
+                Some(foreign) => foreign.platform.name(),
+                None => "invalid",
+            },
+        }
+    }
+}
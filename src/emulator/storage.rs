@@ -0,0 +1,25 @@
+pub use storage::data_dir;
+
+pub mod storage {
+    use std::{env, fs, path::PathBuf};
+
+    use crate::emulator::Chip8Result;
+
+    /// Returns the directory rschip8 keeps its files in, creating it if needed.
+    /// `RSCHIP8_HOME` takes precedence over `~/.rschip8`.
+    pub fn data_dir(subdir: &str) -> Chip8Result<PathBuf> {
+        let root = match env::var_os("RSCHIP8_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let home = env::var_os("HOME")
+                    .or_else(|| env::var_os("USERPROFILE"))
+                    .ok_or("Cannot determine the home directory, set RSCHIP8_HOME instead")?;
+                PathBuf::from(home).join(".rschip8")
+            }
+        };
+
+        let dir = root.join(subdir);
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+}
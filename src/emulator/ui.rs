@@ -2,83 +2,643 @@ pub use ui::Screen;
 pub use ui::Buffer;
 pub use ui::WIDTH;
 pub use ui::HEIGHT;
+pub use ui::HEIGHTS;
 pub use ui::DisplayBuffer;
+pub use ui::INVERT_KEY;
+pub use ui::LCD_KEY;
 
 pub mod ui {
     use sdl2::Sdl;
+    use sdl2::VideoSubsystem;
     use sdl2::video::Window;
     use sdl2::render::Canvas;
     use sdl2::pixels::Color;
     use sdl2::rect::Rect;
+    use sdl2::keyboard::Keycode;
+    use sdl2::messagebox::{self, ButtonData, ClickedButton, MessageBoxButtonFlag, MessageBoxFlag};
+    use serde::{Deserialize, Serialize};
 
-    use crate::emulator::Chip8Result;
+    use crate::emulator::{Chip8Result, heatmap::HeatMap, pacing::{self, FramePacing}, pause::{self, PauseItem, PauseMenu}, remap::{self, RemapMenu}, settings::Palette, slots::{self, SlotMenu, SlotMenuMode}, state::SLOTS};
 
     pub const WIDTH: i32 = 64;
+    /// The height of the standard display
     pub const HEIGHT: i32 = 32;
+    /// The heights displays can have: the standard one, the ETI-660's and the two-page
+    /// display some COSMAC VIP programs use
+    pub const HEIGHTS: [usize; 3] = [32, 48, 64];
+    const MAX_HEIGHT: usize = 64;
     const PIXEL_WH: i32 = 10;
     const SCREEN_WIDTH: u32 = WIDTH as u32 * PIXEL_WH as u32;
-    const SCREEN_HEIGHT: u32 = HEIGHT as u32 * PIXEL_WH as u32;
 
     const BACKGROUND: (u8, u8, u8) = (0, 0, 0);
     const FILL: (u8, u8, u8) = (255, 255, 255);
+    const HIGHLIGHT: (u8, u8, u8) = (255, 200, 0);
+    const DIMMED: (u8, u8, u8) = (96, 96, 96);
 
-    pub type Buffer = [[bool; WIDTH as usize]; HEIGHT as usize];
+    const GLYPH_WIDTH: i32 = 3;
+    const GLYPH_HEIGHT: i32 = 5;
+    const TEXT_SCALE: i32 = 2;
+    const THUMBNAIL_PIXEL_WH: i32 = 2;
+    /// Bytes per row of the memory heat map, which makes 4KB fit in a 64x32 window
+    const HEAT_MAP_COLUMNS: usize = 128;
+    /// Height of the frame pacing graph, which spans two frames' worth of time
+    const PACING_GRAPH_HEIGHT: i32 = 96;
+    const FRAME_MS: f32 = 1000.0 / 60.0;
+    const SLEEP_COLOR: (u8, u8, u8) = (0, 128, 255);
+    const LATE_COLOR: (u8, u8, u8) = (255, 64, 64);
+    /// The DPI at which the window is drawn at its nominal size
+    const BASE_DPI: f32 = 96.0;
+
+    pub const INVERT_KEY: Keycode = Keycode::F10;
+    pub const LCD_KEY: Keycode = Keycode::F11;
+    const LCD_PIXEL: (u8, u8, u8) = (40, 52, 36);
+    const LCD_BACKGROUND: (u8, u8, u8) = (196, 207, 161);
+    /// How much of its darkness an LCD pixel keeps each frame after it's turned off
+    const LCD_PERSISTENCE: f32 = 0.55;
+    const THUMBNAIL_WIDTH: i32 = WIDTH * THUMBNAIL_PIXEL_WH;
+
+    /// The display's pixels, one bit per pixel with each row packed into a `u64`
+    /// whose most significant bit is the leftmost pixel.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+    pub struct Buffer {
+        #[serde(with = "rows")]
+        rows: [u64; MAX_HEIGHT],
+        height: usize,
+    }
+
+    impl Buffer {
+        pub fn new() -> Buffer {
+            Buffer::with_height(HEIGHT as usize)
+        }
+
+        /// Creates a blank display `height` rows high, which must be one of `HEIGHTS`.
+        pub fn with_height(height: usize) -> Buffer {
+            assert!(HEIGHTS.contains(&height), "Unsupported display height: {}", height);
+            Buffer { rows: [0; MAX_HEIGHT], height }
+        }
+
+        pub fn height(&self) -> usize {
+            self.height
+        }
+
+        pub fn clear(&mut self) {
+            self.rows = [0; MAX_HEIGHT];
+        }
+
+        pub fn get(&self, x: usize, y: usize) -> bool {
+            self.rows[y] & (1 << (WIDTH as usize - 1 - x)) != 0
+        }
+
+        pub fn rows(&self) -> &[u64] {
+            &self.rows[..self.height]
+        }
+
+        pub fn set_row(&mut self, y: usize, row: u64) {
+            self.rows[y] = row;
+        }
+
+        /// XORs `bits` into row `y`, returning whether any lit pixel was turned off.
+        pub fn xor_row(&mut self, y: usize, bits: u64) -> bool {
+            let collided = self.rows[y] & bits != 0;
+            self.rows[y] ^= bits;
+            collided
+        }
+
+        /// Iterates over the coordinates of the lit pixels, row by row.
+        pub fn lit_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+            self.rows().iter().enumerate().flat_map(|(y, row)| {
+                (0..WIDTH as usize)
+                    .filter(move |x| row & (1 << (WIDTH as usize - 1 - x)) != 0)
+                    .map(move |x| (x, y))
+            })
+        }
+    }
+
+    impl Default for Buffer {
+        fn default() -> Buffer {
+            Buffer::new()
+        }
+    }
+
+    /// Rows are (de)serialized as a sequence, up to the last one with any lit pixels,
+    /// since serde only supports arrays of up to 32 elements.
+    mod rows {
+        use serde::{Deserialize, Deserializer, Serializer, de::Error};
+        use super::MAX_HEIGHT;
+
+        pub fn serialize<S: Serializer>(rows: &[u64; MAX_HEIGHT], serializer: S) -> Result<S::Ok, S::Error> {
+            let used = rows.iter().rposition(|row| *row != 0).map_or(0, |y| y + 1);
+            serializer.collect_seq(rows[..used].iter())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u64; MAX_HEIGHT], D::Error> {
+            let rows: Vec<u64> = Deserialize::deserialize(deserializer)?;
+            if rows.len() > MAX_HEIGHT {
+                return Err(D::Error::invalid_length(rows.len(), &"at most 64 rows"));
+            }
+            let mut out = [0; MAX_HEIGHT];
+            out[..rows.len()].copy_from_slice(&rows);
+            Ok(out)
+        }
+    }
 
     pub struct Screen {
         canvas: Canvas<Window>,
+        /// Rows of the display the window is sized for
+        height: i32,
+        palette: Palette,
         pub display_buffer: DisplayBuffer,
+        /// How dark each pixel of the LCD still is, from 0 to 1, row by row
+        ghosting: Vec<f32>,
     }
 
+    #[derive(Clone, Copy, Serialize, Deserialize)]
     pub struct DisplayBuffer {
         pub buffer: Buffer,
         pub is_dirty: bool,
     }
 
     impl Screen {
-        pub fn new(sdl_context: &Sdl, title: &str) -> Chip8Result<Screen> {
+        pub fn new(sdl_context: &Sdl, title: &str, palette: Palette, height: usize) -> Chip8Result<Screen> {
             let video_subsystem = sdl_context.video()?;
-            let window = video_subsystem.window(title, SCREEN_WIDTH, SCREEN_HEIGHT)
+            let scale = display_scale(&video_subsystem);
+            let screen_height = height as u32 * PIXEL_WH as u32;
+            let window = video_subsystem.window(title, SCREEN_WIDTH * scale, screen_height * scale)
                 .position_centered()
+                .allow_highdpi()
                 .build()?;
 
-            let canvas = window.into_canvas().build()?;
+            // Everything is drawn at the nominal size and scaled by a whole factor, which covers
+            // both the window being enlarged and displays with more pixels than window units
+            let mut canvas = window.into_canvas().build()?;
+            let (output_width, _) = canvas.output_size()?;
+            let render_scale = (output_width / SCREEN_WIDTH).max(1) as f32;
+            canvas.set_scale(render_scale, render_scale)?;
             let display_buffer = DisplayBuffer {
-                buffer: [[false; WIDTH as usize]; HEIGHT as usize],
+                buffer: Buffer::with_height(height),
                 is_dirty: true,
             };
 
             Ok(Screen {
                 canvas,
+                height: height as i32,
+                palette,
                 display_buffer,
+                ghosting: vec![0.0; WIDTH as usize * MAX_HEIGHT],
             })
         }
 
+        /// Gets the window ready for another program, the way `new` would have left it, keeping
+        /// the scale it was resized to.
+        pub fn reopen(&mut self, title: &str, palette: Palette, height: usize) -> Chip8Result<()> {
+            let window = self.canvas.window_mut();
+            window.set_title(title)?;
+            if height as i32 != self.height {
+                let (width, window_height) = window.size();
+                window.set_size(width, window_height / self.height as u32 * height as u32)?;
+            }
+
+            self.height = height as i32;
+            self.palette = palette;
+            self.display_buffer = DisplayBuffer {
+                buffer: Buffer::with_height(height),
+                is_dirty: true,
+            };
+            self.ghosting.iter_mut().for_each(|dark| *dark = 0.0);
+            Ok(())
+        }
+
+        /// Shows a modal yes/no question, returning whether it was answered with yes.
+        pub fn ask(&self, title: &str, message: &str) -> Chip8Result<bool> {
+            let buttons = [
+                ButtonData { flags: MessageBoxButtonFlag::RETURNKEY_DEFAULT, button_id: 1, text: "Yes" },
+                ButtonData { flags: MessageBoxButtonFlag::ESCAPEKEY_DEFAULT, button_id: 0, text: "No" },
+            ];
+            let clicked = messagebox::show_message_box(
+                MessageBoxFlag::INFORMATION, &buttons, title, message, self.canvas.window(), None
+            )?;
+
+            Ok(matches!(clicked, ClickedButton::CustomButton(ButtonData { button_id: 1, .. })))
+        }
+
+        /// Replaces the frame to be drawn.
+        pub fn show(&mut self, buffer: &Buffer) {
+            self.display_buffer.buffer = *buffer;
+            self.display_buffer.is_dirty = true;
+        }
+
+        pub fn toggle_inverted(&mut self) {
+            self.palette.inverted = !self.palette.inverted;
+            self.display_buffer.is_dirty = true;
+        }
+
+        pub fn toggle_lcd(&mut self) {
+            self.palette.lcd = !self.palette.lcd;
+            self.display_buffer.is_dirty = true;
+        }
+
+        pub fn palette(&self) -> Palette {
+            self.palette
+        }
+
+        pub fn set_look(&mut self, inverted: bool, lcd: bool) {
+            self.palette.inverted = inverted;
+            self.palette.lcd = lcd;
+            self.display_buffer.is_dirty = true;
+        }
+
         pub fn draw(&mut self) -> Chip8Result<()> {
             if !self.display_buffer.is_dirty {
                 return Ok(());
             }
 
+            self.draw_display()?;
+            self.canvas.present();
+            self.display_buffer.is_dirty = false;
+
+            Ok(())
+        }
+
+        /// Lets the LCD's pixels that were turned off fade for a frame, to be called 60 times
+        /// per second. The frame is marked dirty while they do, so they fade out even when
+        /// the program has stopped drawing.
+        pub fn fade(&mut self) {
+            if !self.palette.lcd {
+                return;
+            }
+            let buffer = self.display_buffer.buffer;
+            for y in 0..buffer.height() {
+                for x in 0..WIDTH as usize {
+                    let darkness = &mut self.ghosting[y * WIDTH as usize + x];
+                    if buffer.get(x, y) {
+                        *darkness = 1.0;
+                    } else if *darkness > 0.0 {
+                        *darkness = if *darkness > 0.05 { *darkness * LCD_PERSISTENCE } else { 0.0 };
+                        self.display_buffer.is_dirty = true;
+                    }
+                }
+            }
+        }
+
+        fn draw_display(&mut self) -> Chip8Result<()> {
+            let buffer = self.display_buffer.buffer;
+            if self.palette.lcd {
+                self.draw_lcd(&buffer)
+            } else {
+                self.clear(self.colors().1);
+                self.draw_buffer(&buffer, 0, 0, PIXEL_WH)
+            }
+        }
+
+        /// Draws the program's frame with a graph of the last frames over its bottom: a bar
+        /// for the time each frame took, red when it ran late, the part of it spent sleeping
+        /// in blue, and a mark for the instructions executed against `expected_cycles`.
+        /// The display buffer is marked dirty so the frame is redrawn without the graph.
+        pub fn draw_pacing(&mut self, pacing: &FramePacing, expected_cycles: u32) -> Chip8Result<()> {
+            self.draw_display()?;
+
+            let line_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+            let bottom = self.height * PIXEL_WH;
+            let top = bottom - PACING_GRAPH_HEIGHT - line_height - 8;
             self.canvas.set_draw_color(Color::RGB(BACKGROUND.0, BACKGROUND.1, BACKGROUND.2));
-            self.canvas.clear();
+            self.canvas.fill_rect(Rect::new(0, top, SCREEN_WIDTH, (bottom - top) as u32))?;
+
+            if let Some(average) = pacing.average() {
+                self.draw_text(&format!("FRAME {:.1}MS  SLEEP {:.1}MS  CYCLES {}/{}",
+                    average.frame.as_secs_f32() * 1000.0, average.sleep.as_secs_f32() * 1000.0,
+                    average.cycles, expected_cycles), FILL, 8, top + 4)?;
+            }
+
+            let bar_width = SCREEN_WIDTH as i32 / pacing::HISTORY as i32;
+            let scale = PACING_GRAPH_HEIGHT as f32 / (2.0 * FRAME_MS);
+            let height = |ms: f32| ((ms * scale) as i32).clamp(1, PACING_GRAPH_HEIGHT);
+            for (i, sample) in pacing.samples().enumerate() {
+                let x = i as i32 * bar_width;
+                let frame_ms = sample.frame.as_secs_f32() * 1000.0;
+                let color = if frame_ms > 1.5 * FRAME_MS { LATE_COLOR } else { DIMMED };
+                self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+                let frame_height = height(frame_ms);
+                self.canvas.fill_rect(Rect::new(x, bottom - frame_height, bar_width as u32 - 1, frame_height as u32))?;
+
+                self.canvas.set_draw_color(Color::RGB(SLEEP_COLOR.0, SLEEP_COLOR.1, SLEEP_COLOR.2));
+                let sleep_height = height(sample.sleep.as_secs_f32() * 1000.0);
+                self.canvas.fill_rect(Rect::new(x, bottom - sleep_height, bar_width as u32 - 1, sleep_height as u32))?;
+
+                // Executing as many instructions as expected puts the mark on the 60Hz line
+                let cycles = sample.cycles as f32 / expected_cycles.max(1) as f32 * FRAME_MS;
+                self.canvas.set_draw_color(Color::RGB(HIGHLIGHT.0, HIGHLIGHT.1, HIGHLIGHT.2));
+                self.canvas.fill_rect(Rect::new(x, bottom - height(cycles) - 1, bar_width as u32 - 1, 2))?;
+            }
 
             self.canvas.set_draw_color(Color::RGB(FILL.0, FILL.1, FILL.2));
+            let target = bottom - height(FRAME_MS);
+            self.canvas.draw_line((0, target), (SCREEN_WIDTH as i32, target))?;
+
+            self.canvas.present();
+            self.display_buffer.is_dirty = true;
 
-            for (i, cols) in self.display_buffer.buffer.iter().enumerate() {
-                for (j, is_on) in cols.iter().enumerate() {
-                    if *is_on {
-                        self.canvas.fill_rect(Rect::new(
-                            j as i32 * PIXEL_WH,
-                            i as i32 * PIXEL_WH,
-                            PIXEL_WH as u32,
-                            PIXEL_WH as u32
-                        ))?;
+            Ok(())
+        }
+
+        /// The foreground and background colors to draw with.
+        fn colors(&self) -> ((u8, u8, u8), (u8, u8, u8)) {
+            let (foreground, background) = if self.palette.lcd {
+                (LCD_PIXEL, LCD_BACKGROUND)
+            } else {
+                (self.palette.foreground, self.palette.background)
+            };
+            if self.palette.inverted { (background, foreground) } else { (foreground, background) }
+        }
+
+        /// Draws the display the way an LCD shows it, with pixels that were just turned
+        /// off still partly dark while they fade out, see `fade`.
+        fn draw_lcd(&mut self, buffer: &Buffer) -> Chip8Result<()> {
+            let (foreground, background) = self.colors();
+            self.clear(background);
+
+            for y in 0..buffer.height() {
+                for x in 0..WIDTH as usize {
+                    let darkness = if buffer.get(x, y) { 1.0 } else { self.ghosting[y * WIDTH as usize + x] };
+                    if darkness == 0.0 {
+                        continue;
+                    }
+
+                    let blend = |fg: u8, bg: u8| (bg as f32 + (fg as f32 - bg as f32) * darkness) as u8;
+                    self.canvas.set_draw_color(Color::RGB(
+                        blend(foreground.0, background.0), blend(foreground.1, background.1), blend(foreground.2, background.2)
+                    ));
+                    self.canvas.fill_rect(Rect::new(
+                        x as i32 * PIXEL_WH, y as i32 * PIXEL_WH, PIXEL_WH as u32, PIXEL_WH as u32
+                    ))?;
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Draws the save state slot menu over the whole window. The display buffer is
+        /// marked dirty so the program's frame is restored once the menu is closed.
+        pub fn draw_slot_menu(&mut self, menu: &SlotMenu) -> Chip8Result<()> {
+            self.clear(BACKGROUND);
+
+            let title = match menu.mode {
+                SlotMenuMode::Save => "SAVE STATE",
+                SlotMenuMode::Load => "LOAD STATE",
+            };
+            self.draw_text(title, FILL, 8, 8)?;
+
+            let columns = slots::COLUMNS as i32;
+            let rows = SLOTS.div_ceil(slots::COLUMNS) as i32;
+            let line_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+            let screen_height = self.height * PIXEL_WH;
+            let thumbnail_height = self.height * THUMBNAIL_PIXEL_WH;
+            let cell_height = thumbnail_height + 2 * line_height + 8;
+            let gap_x = (SCREEN_WIDTH as i32 - columns * THUMBNAIL_WIDTH) / (columns + 1);
+            let top = 8 + 2 * line_height;
+            let gap_y = (screen_height - top - line_height - rows * cell_height) / (rows + 1);
+
+            for (slot, state) in menu.slots.iter().enumerate() {
+                let x = gap_x + (slot as i32 % columns) * (THUMBNAIL_WIDTH + gap_x);
+                let y = top + gap_y + (slot as i32 / columns) * (cell_height + gap_y);
+                let color = if slot == menu.selected { HIGHLIGHT } else { FILL };
+
+                self.draw_text(&format!("SLOT {}", slot + 1), color, x, y)?;
+                let thumbnail_y = y + line_height;
+                match state {
+                    Some(state) => {
+                        self.draw_buffer(state.buffer(), x, thumbnail_y, THUMBNAIL_PIXEL_WH)?;
+                        self.draw_text(&state.formatted_timestamp(), color,
+                            x, thumbnail_y + thumbnail_height + 4)?;
+                    }
+                    None => {
+                        self.draw_text("EMPTY", DIMMED, x + 4, thumbnail_y + 4)?;
                     }
                 }
+
+                self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+                self.canvas.draw_rect(Rect::new(
+                    x - 2, thumbnail_y - 2, THUMBNAIL_WIDTH as u32 + 4, thumbnail_height as u32 + 4
+                ))?;
+            }
+
+            self.draw_text("ARROWS SELECT   ENTER CONFIRM   ESC CANCEL", DIMMED,
+                8, screen_height - line_height)?;
+
+            self.canvas.present();
+            self.display_buffer.is_dirty = true;
+
+            Ok(())
+        }
+
+        /// Draws the memory heat map over the whole window, one cell per byte. Like the slot
+        /// menu, it marks the display buffer dirty so the program's frame returns when it's closed.
+        pub fn draw_heat_map(&mut self, heat_map: &HeatMap, memory: &[u8]) -> Chip8Result<()> {
+            self.clear(BACKGROUND);
+
+            let line_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+            let cell_wh = SCREEN_WIDTH as i32 / HEAT_MAP_COLUMNS as i32;
+            let top = 8 + line_height;
+            self.draw_text("MEMORY", FILL, 8, 8)?;
+
+            for (addr, value) in memory.iter().enumerate() {
+                let (r, g, b) = heat_map.color(addr, *value);
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+                self.canvas.fill_rect(Rect::new(
+                    (addr % HEAT_MAP_COLUMNS) as i32 * cell_wh,
+                    top + (addr / HEAT_MAP_COLUMNS) as i32 * cell_wh,
+                    cell_wh as u32,
+                    cell_wh as u32
+                ))?;
+            }
+
+            let legend_y = top + (memory.len() / HEAT_MAP_COLUMNS) as i32 * cell_wh + 8;
+            self.draw_text("READ", (0, 255, 0), 8, legend_y)?;
+            self.draw_text("WRITE", (255, 0, 0), 8 + 6 * (GLYPH_WIDTH + 1) * TEXT_SCALE, legend_y)?;
+            self.draw_text("EXECUTE", (0, 0, 255), 8 + 13 * (GLYPH_WIDTH + 1) * TEXT_SCALE, legend_y)?;
+
+            self.canvas.present();
+            self.display_buffer.is_dirty = true;
+
+            Ok(())
+        }
+
+        /// Draws the key remapping overlay: the keypad with the host key of each CHIP-8 key,
+        /// and the key being asked for highlighted.
+        pub fn draw_remap_menu(&mut self, menu: &RemapMenu) -> Chip8Result<()> {
+            self.clear(BACKGROUND);
+
+            let line_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+            let screen_height = self.height * PIXEL_WH;
+            let cell_width = SCREEN_WIDTH as i32 / 4;
+            let current = remap::KEYPAD[menu.current];
+            self.draw_text("REMAP KEYS", FILL, 8, 8)?;
+            self.draw_text(&format!("PRESS THE KEY FOR {:X}", current.chip8_code()), HIGHLIGHT, 8, 8 + line_height)?;
+
+            let top = 8 + 3 * line_height;
+            for (i, key) in remap::KEYPAD.iter().enumerate() {
+                let color = if *key == current { HIGHLIGHT } else { FILL };
+                let host = menu.host_key(*key).unwrap_or_else(|| "-".to_string());
+                self.draw_text(&format!("{:X}: {}", key.chip8_code(), host), color,
+                    8 + (i as i32 % 4) * cell_width, top + (i as i32 / 4) * 2 * line_height)?;
             }
 
+            self.draw_text("BACKSPACE KEEP   ESC CANCEL", DIMMED, 8, screen_height - line_height)?;
+
             self.canvas.present();
+            self.display_buffer.is_dirty = true;
+
+            Ok(())
+        }
+
+        /// Draws the pause menu over the whole window, with the selected item highlighted.
+        pub fn draw_pause_menu(&mut self, menu: &PauseMenu) -> Chip8Result<()> {
+            self.clear(BACKGROUND);
+
+            let line_height = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+            let screen_height = self.height * PIXEL_WH;
+            self.draw_text("PAUSED", FILL, 8, 8)?;
+
+            let top = 8 + 2 * line_height;
+            for (i, item) in pause::ITEMS.iter().enumerate() {
+                let text = match item {
+                    PauseItem::Resume => "RESUME".to_string(),
+                    PauseItem::Reset => "RESET".to_string(),
+                    PauseItem::SaveState => "SAVE STATE".to_string(),
+                    PauseItem::LoadState => "LOAD STATE".to_string(),
+                    PauseItem::Speed => format!("SPEED    < {} >", menu.speed),
+                    PauseItem::Look => format!("LOOK     < {} >", menu.look_name()),
+                    PauseItem::RemapKeys => "REMAP KEYS".to_string(),
+                    PauseItem::Quit => "QUIT".to_string(),
+                };
+                let color = if i == menu.selected { HIGHLIGHT } else { FILL };
+                self.draw_text(&text, color, 16, top + i as i32 * line_height)?;
+            }
+
+            self.draw_text("ARROWS SELECT   ENTER CONFIRM   ESC RESUME", DIMMED, 8, screen_height - line_height)?;
+
+            self.canvas.present();
+            self.display_buffer.is_dirty = true;
+
+            Ok(())
+        }
+
+        fn clear(&mut self, color: (u8, u8, u8)) {
+            self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+            self.canvas.clear();
+        }
+
+        fn draw_buffer(&mut self, buffer: &Buffer, x: i32, y: i32, pixel_wh: i32) -> Chip8Result<()> {
+            let (foreground, background) = self.colors();
+            self.canvas.set_draw_color(Color::RGB(background.0, background.1, background.2));
+            self.canvas.fill_rect(Rect::new(
+                x, y, (WIDTH * pixel_wh) as u32, (buffer.height() as i32 * pixel_wh) as u32
+            ))?;
+
+            self.canvas.set_draw_color(Color::RGB(foreground.0, foreground.1, foreground.2));
+
+            for (j, i) in buffer.lit_pixels() {
+                self.canvas.fill_rect(Rect::new(
+                    x + j as i32 * pixel_wh,
+                    y + i as i32 * pixel_wh,
+                    pixel_wh as u32,
+                    pixel_wh as u32
+                ))?;
+            }
 
             Ok(())
         }
+
+        fn draw_text(&mut self, text: &str, color: (u8, u8, u8), x: i32, y: i32) -> Chip8Result<()> {
+            self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+
+            for (n, c) in text.chars().enumerate() {
+                let left = x + n as i32 * (GLYPH_WIDTH + 1) * TEXT_SCALE;
+                for (i, row) in glyph(c).iter().enumerate() {
+                    for j in 0..GLYPH_WIDTH {
+                        if row & (0b100 >> j) != 0 {
+                            self.canvas.fill_rect(Rect::new(
+                                left + j * TEXT_SCALE,
+                                y + i as i32 * TEXT_SCALE,
+                                TEXT_SCALE as u32,
+                                TEXT_SCALE as u32
+                            ))?;
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+    /// Returns the rows of a 3x5 glyph, most significant of the three bits on the left.
+    fn glyph(c: char) -> [u8; 5] {
+        match c.to_ascii_uppercase() {
+            'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+            'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+            'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+            'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+            'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+            'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+            'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+            'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+            'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+            'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+            'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+            'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+            'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+            'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+            'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+            'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+            'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+            'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+            'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+            'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+            'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+            'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+            'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+            'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+            'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+            'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+            '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+            '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+            '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+            '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+            '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+            '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+            '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+            '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+            '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+            '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+            ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+            ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+            '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+            '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+            ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+            '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+            '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+            ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+            '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+            ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+            '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+            '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+            '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+            '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+            '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+            '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+            '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+            '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+            '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+            '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+            _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+        }
+    }
+
+    /// How many times larger than its nominal size the window should be to look the same
+    /// on the primary display as it does on a standard DPI one.
+    fn display_scale(video_subsystem: &VideoSubsystem) -> u32 {
+        match video_subsystem.display_dpi(0) {
+            Ok((dpi, _, _)) => ((dpi / BASE_DPI).round() as u32).max(1),
+            Err(_) => 1,
+        }
     }
 }
@@ -0,0 +1,160 @@
+pub use variants::Platform;
+pub use variants::ForeignOpcode;
+pub use variants::Detection;
+
+pub mod variants {
+    use std::fmt;
+
+    use crate::emulator::{Chip8Result, analysis, quirks::Quirks};
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Platform {
+        Chip8,
+        CosmacVip,
+        SuperChip,
+        XoChip,
+        MegaChip,
+    }
+
+    impl Platform {
+        pub const ALL: [Platform; 5] = [
+            Platform::Chip8, Platform::CosmacVip, Platform::SuperChip, Platform::XoChip, Platform::MegaChip
+        ];
+
+        /// The name used to select the platform on the command line
+        pub fn name(&self) -> &'static str {
+            match self {
+                Platform::Chip8 => "chip8",
+                Platform::CosmacVip => "vip",
+                Platform::SuperChip => "schip",
+                Platform::XoChip => "xochip",
+                Platform::MegaChip => "megachip",
+            }
+        }
+
+        pub fn from_name(name: &str) -> Chip8Result<Platform> {
+            Platform::ALL.iter()
+                .find(|platform| platform.name() == name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown platform: {}", name).into())
+        }
+
+        /// The quirks programs written for the platform expect. `Chip8` is the behavior
+        /// most interpreters settled on, which is what rschip8 has always done.
+        pub fn quirks(&self) -> Quirks {
+            match self {
+                Platform::Chip8 => Quirks {
+                    shift_vy: false,
+                    memory_increments_i: false,
+                    jump_vx: false,
+                    vf_reset: false,
+                    clip_sprites: false,
+                },
+                Platform::CosmacVip => Quirks {
+                    shift_vy: true,
+                    memory_increments_i: true,
+                    jump_vx: false,
+                    vf_reset: true,
+                    clip_sprites: true,
+                },
+                Platform::SuperChip | Platform::MegaChip => Quirks {
+                    shift_vy: false,
+                    memory_increments_i: false,
+                    jump_vx: true,
+                    vf_reset: false,
+                    clip_sprites: true,
+                },
+                Platform::XoChip => Quirks {
+                    shift_vy: true,
+                    memory_increments_i: true,
+                    jump_vx: false,
+                    vf_reset: false,
+                    clip_sprites: false,
+                },
+            }
+        }
+    }
+
+    impl fmt::Display for Platform {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match self {
+                Platform::Chip8 => "CHIP-8",
+                Platform::CosmacVip => "COSMAC VIP",
+                Platform::SuperChip => "SUPER-CHIP",
+                Platform::XoChip => "XO-CHIP",
+                Platform::MegaChip => "MEGA-CHIP",
+            })
+        }
+    }
+
+    /// An opcode that isn't part of CHIP-8 but belongs to one of its extensions.
+    #[derive(Clone, Copy, Debug)]
+    pub struct ForeignOpcode {
+        pub opcode: u16,
+        pub platform: Platform,
+        pub description: &'static str,
+    }
+
+    impl ForeignOpcode {
+        /// Recognizes opcodes of the SUPER-CHIP, XO-CHIP and MEGA-CHIP extensions.
+        pub fn identify(opcode: u16) -> Option<ForeignOpcode> {
+            let (platform, description) = match (opcode >> 12, (opcode >> 8) & 0xf, (opcode >> 4) & 0xf, opcode & 0xf) {
+                (0, 0, 0xc, _) => (Platform::SuperChip, "scroll down"),
+                (0, 0, 0xf, 0xb) => (Platform::SuperChip, "scroll right"),
+                (0, 0, 0xf, 0xc) => (Platform::SuperChip, "scroll left"),
+                (0, 0, 0xf, 0xd) => (Platform::SuperChip, "exit"),
+                (0, 0, 0xf, 0xe) => (Platform::SuperChip, "low resolution mode"),
+                (0, 0, 0xf, 0xf) => (Platform::SuperChip, "high resolution mode"),
+                (0xf, _, 3, 0) => (Platform::SuperChip, "point I at a large font digit"),
+                (0xf, _, 7, 5) => (Platform::SuperChip, "save registers to flags"),
+                (0xf, _, 8, 5) => (Platform::SuperChip, "load registers from flags"),
+                (0, 0, 0xd, _) => (Platform::XoChip, "scroll up"),
+                (5, _, _, 2) => (Platform::XoChip, "save register range"),
+                (5, _, _, 3) => (Platform::XoChip, "load register range"),
+                (0xf, 0, 0, 0) => (Platform::XoChip, "load 16-bit address into I"),
+                (0xf, 0..=3, 0, 1) => (Platform::XoChip, "select drawing planes"),
+                (0xf, 0, 0, 2) => (Platform::XoChip, "load audio pattern"),
+                (0xf, _, 3, 0xa) => (Platform::XoChip, "set audio pitch"),
+                (0, 0, 1, 0) => (Platform::MegaChip, "disable MEGA-CHIP mode"),
+                (0, 0, 1, 1) => (Platform::MegaChip, "enable MEGA-CHIP mode"),
+                _ => return None,
+            };
+
+            Some(ForeignOpcode { opcode, platform, description })
+        }
+    }
+
+    impl fmt::Display for ForeignOpcode {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:04X} is a {} opcode ({}), but rschip8 only supports CHIP-8 programs",
+                self.opcode, self.platform, self.description)
+        }
+    }
+
+    /// The result of scanning a ROM for opcodes of CHIP-8 extensions.
+    pub struct Detection {
+        pub platform: Platform,
+        /// Opcodes of the platform found in reachable code, along with their addresses
+        pub evidence: Vec<(usize, ForeignOpcode)>,
+    }
+
+    impl Detection {
+        /// Looks for extension opcodes in the code reachable from the entry point, so that
+        /// sprite data which happens to look like an opcode doesn't count. When opcodes of
+        /// more than one extension turn up, the one found most often wins.
+        pub fn scan(rom: &[u8]) -> Option<Detection> {
+            let mut evidence: Vec<(usize, ForeignOpcode)> = analysis::reachable_instructions(rom)
+                .into_iter()
+                .filter_map(|(addr, opcode)| Some((addr, ForeignOpcode::identify(opcode)?)))
+                .collect();
+
+            let platform = Platform::ALL.iter()
+                .cloned()
+                .filter(|platform| evidence.iter().any(|(_, foreign)| foreign.platform == *platform))
+                .max_by_key(|platform| evidence.iter().filter(|(_, foreign)| foreign.platform == *platform).count())?;
+            evidence.retain(|(_, foreign)| foreign.platform == platform);
+
+            Some(Detection { platform, evidence })
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use std::env;
-mod emulator;
+use rschip8::emulator::{self, config::{Config, USAGE}};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: rschip8 <PROGRAM>");
-        return;
-    }
-    if let Err(e) = emulator::run(&args[1]) {
+    let config = match Config::from_args(&args[1..]) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return;
+        }
+    };
+    if let Err(e) = emulator::run(&config) {
         eprintln!("Error: {}", e);
     }
 }